@@ -0,0 +1,19 @@
+#![no_main]
+use av_format::buffer::AccReader;
+use av_format::demuxer::{Context, Event};
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+use y4m::demuxer::Y4MDemuxer;
+
+fuzz_target!(|data: &[u8]| {
+    let demuxer = Y4MDemuxer::new().with_input_len(data.len() as u64);
+    let input = Box::new(AccReader::new(Cursor::new(data.to_vec())));
+    let mut demuxer = Context::new(Box::new(demuxer), input);
+
+    // Drive the same loops as `av_format` users, so that a demuxer asking for
+    // data that never comes shows up as a hang
+    if demuxer.read_headers().is_err() {
+        return;
+    }
+    while let Ok(Event::NewPacket(_)) = demuxer.read_event() {}
+});
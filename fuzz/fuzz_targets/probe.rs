@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use y4m::demuxer::Y4M_DESC;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Y4M_DESC.probe(data);
+});
@@ -0,0 +1,227 @@
+//! Asynchronous counterparts of [`Reader`](crate::Reader) and
+//! [`Writer`](crate::Writer), built on the `tokio` I/O traits
+
+use crate::demuxer::{FrameParams, Limits, ParseMode, Y4MHeader};
+use crate::error::Y4MError;
+use crate::reader::{header_line_len, Frame, ReadState};
+use crate::writer::{check_frame, plane_rows, WriterBuilder};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Reads a YUV4MPEG2 stream from an `AsyncRead`
+///
+/// Same as [`Reader`](crate::Reader), lines are read one byte at a time, wrap
+/// unbuffered inputs such as sockets in a `BufReader`.
+pub struct AsyncReader<R: AsyncRead + Unpin> {
+    input: R,
+    state: ReadState,
+}
+
+impl<R: AsyncRead + Unpin> AsyncReader<R> {
+    /// Reads the stream header with the default mode and limits
+    pub async fn new(input: R) -> io::Result<AsyncReader<R>> {
+        AsyncReader::with_options(input, ParseMode::default(), Limits::default()).await
+    }
+
+    /// Same as [`Reader::with_options`](crate::Reader::with_options)
+    pub async fn with_options(
+        mut input: R,
+        mode: ParseMode,
+        limits: Limits,
+    ) -> io::Result<AsyncReader<R>> {
+        let mut line = Vec::new();
+        read_line(&mut input, &mut line, header_line_len(&limits)).await?;
+        let state = ReadState::new(line, mode, limits)?;
+
+        Ok(AsyncReader { input, state })
+    }
+
+    pub fn header(&self) -> &Y4MHeader {
+        &self.state.header
+    }
+
+    /// Same as [`Reader::warnings`](crate::Reader::warnings)
+    pub fn warnings(&self) -> &[Y4MError] {
+        &self.state.warnings
+    }
+
+    /// Same as [`Reader::next_frame`](crate::Reader::next_frame)
+    pub async fn next_frame(&mut self) -> io::Result<Option<Frame<'_>>> {
+        let params = match self.read_frame_header().await? {
+            Some(params) => params,
+            None => return Ok(None),
+        };
+        let data = self.state.frame_buffer();
+        self.input.read_exact(data).await?;
+
+        Ok(Some(self.state.frame(params)))
+    }
+
+    /// Same as [`Reader::read_frame_into`](crate::Reader::read_frame_into)
+    pub async fn read_frame_into(&mut self, frame: &mut Frame<'static>) -> io::Result<bool> {
+        let params = match self.read_frame_header().await? {
+            Some(params) => params,
+            None => return Ok(false),
+        };
+        let data = self.state.frame_buffer_in(frame);
+        self.input.read_exact(data).await?;
+        self.state.fill_frame(frame, params);
+
+        Ok(true)
+    }
+
+    async fn read_frame_header(&mut self) -> io::Result<Option<FrameParams>> {
+        let max = self.state.frame_line_len();
+        read_line(&mut self.input, &mut self.state.line, max).await?;
+        self.state.frame_params()
+    }
+
+    /// Returns the underlying reader, positioned at the next frame
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+/// Reads up to the next newline included, at most `max` bytes
+async fn read_line<R: AsyncRead + Unpin>(
+    input: &mut R,
+    line: &mut Vec<u8>,
+    max: usize,
+) -> io::Result<()> {
+    let mut byte = [0u8; 1];
+    line.clear();
+    while line.len() < max && line.last() != Some(&b'\n') {
+        match input.read(&mut byte).await {
+            Ok(0) => break,
+            Ok(_) => line.push(byte[0]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+impl WriterBuilder {
+    /// Validates the configuration and writes the stream header to `output`
+    pub async fn build_async<W: AsyncWrite + Unpin>(
+        self,
+        mut output: W,
+    ) -> io::Result<AsyncWriter<W>> {
+        let header = self.into_header()?;
+        output.write_all(format!("{}\n", header).as_bytes()).await?;
+
+        Ok(AsyncWriter { output, header })
+    }
+}
+
+/// Writes a YUV4MPEG2 stream to an `AsyncWrite`, created by
+/// [`WriterBuilder::build_async`]
+pub struct AsyncWriter<W: AsyncWrite + Unpin> {
+    output: W,
+    header: Y4MHeader,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWriter<W> {
+    pub fn header(&self) -> &Y4MHeader {
+        &self.header
+    }
+
+    /// Writes a frame without parameters
+    pub async fn write_frame(&mut self, planes: &[&[u8]], strides: &[usize]) -> io::Result<()> {
+        self.write_frame_with_params(planes, strides, &FrameParams::default())
+            .await
+    }
+
+    /// Same as [`Writer::write_frame_with_params`](crate::Writer::write_frame_with_params)
+    pub async fn write_frame_with_params(
+        &mut self,
+        planes: &[&[u8]],
+        strides: &[usize],
+        params: &FrameParams,
+    ) -> io::Result<()> {
+        check_frame(&self.header, planes, strides, params)?;
+
+        self.output
+            .write_all(format!("FRAME{}\n", params).as_bytes())
+            .await?;
+        for (plane, (data, &stride)) in planes.iter().zip(strides).enumerate() {
+            for rows in plane_rows(&self.header, plane, data, stride) {
+                self.output.write_all(rows).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.output.flush().await
+    }
+
+    /// Flushes and closes the output
+    pub async fn shutdown(&mut self) -> io::Result<()> {
+        self.output.shutdown().await
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demuxer::{Colorspace, Interlace};
+
+    #[tokio::test]
+    async fn duplex_roundtrip() {
+        // Smaller than a frame, so that both ends have to take turns
+        let (tx, rx) = tokio::io::duplex(16);
+
+        let write = async move {
+            let mut writer = WriterBuilder::new(8, 4)
+                .with_colorspace(Colorspace::C422)
+                .build_async(tx)
+                .await?;
+            let y: Vec<u8> = (0..32).collect();
+            let c: Vec<u8> = (32..48).collect();
+            let params = FrameParams::new().with_interlace(Interlace::TopFieldFirst);
+            for _ in 0..3 {
+                writer
+                    .write_frame_with_params(&[&y, &c, &c], &[8, 4, 4], &params)
+                    .await?;
+            }
+            writer.shutdown().await
+        };
+
+        let read = async move {
+            let mut reader = AsyncReader::new(rx).await?;
+            assert_eq!(reader.header().colorspace(), Colorspace::C422);
+            let mut frame = Frame::default();
+            let mut count = 0;
+            while reader.read_frame_into(&mut frame).await? {
+                assert_eq!(frame.params().interlace(), Some(Interlace::TopFieldFirst));
+                assert_eq!(frame.plane(0).unwrap()[31], 31);
+                assert_eq!(frame.plane(2).unwrap()[0], 32);
+                count += 1;
+            }
+            Ok::<_, io::Error>(count)
+        };
+
+        let (written, read) = tokio::join!(write, read);
+        written.unwrap();
+        assert_eq!(read.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn truncated_stream() {
+        let (mut tx, rx) = tokio::io::duplex(64);
+        tx.write_all(b"YUV4MPEG2 W2 H2 C444\nFRAME\n\0\0")
+            .await
+            .unwrap();
+        drop(tx);
+
+        let mut reader = AsyncReader::new(rx).await.unwrap();
+        let err = reader.next_frame().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,257 @@
+//! Prints the properties of a YUV4MPEG2 stream
+//!
+//! Usage: `y4minfo [--json] [FILE]`, reading stdin if `FILE` is `-` or missing.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::process;
+use y4m::demuxer::{Interlace, Ratio, Y4MHeader};
+use y4m::Reader;
+
+/// What was learnt about a stream
+struct Info {
+    name: String,
+    header: Y4MHeader,
+    warnings: Vec<String>,
+    header_size: usize,
+    frame_count: u64,
+    /// Bytes of the stream read successfully
+    stream_size: usize,
+    /// Size of the input, if it is a file
+    file_size: Option<u64>,
+    /// Error met while reading the frames
+    error: Option<String>,
+}
+
+impl Info {
+    fn duration(&self) -> f64 {
+        let tb = self.header.timebase();
+        self.frame_count as f64 * *tb.numer() as f64 / *tb.denom() as f64
+    }
+
+    /// Whether the frames account for the whole input
+    fn consistent(&self) -> bool {
+        let size = self.stream_size as u64;
+        self.error.is_none() && self.file_size.iter().all(|&s| s == size)
+    }
+}
+
+fn inspect<R: Read>(input: R, name: &str, file_size: Option<u64>) -> io::Result<Info> {
+    let mut reader = Reader::new(input)?;
+    let header_size = reader.position();
+    let mut frame_count = 0;
+    let error = loop {
+        match reader.next_frame() {
+            Ok(Some(_)) => frame_count += 1,
+            Ok(None) => break None,
+            Err(e) => break Some(e.to_string()),
+        }
+    };
+
+    Ok(Info {
+        name: name.to_owned(),
+        header: reader.header().clone(),
+        warnings: reader.warnings().iter().map(|w| w.to_string()).collect(),
+        header_size,
+        frame_count,
+        stream_size: reader.position(),
+        file_size,
+        error,
+    })
+}
+
+fn interlace_name(interlace: Interlace) -> &'static str {
+    match interlace {
+        Interlace::Progressive => "progressive",
+        Interlace::TopFieldFirst => "top field first",
+        Interlace::BottomFieldFirst => "bottom field first",
+        Interlace::Mixed => "mixed",
+        Interlace::Unknown => "unknown",
+    }
+}
+
+fn write_text<W: Write>(out: &mut W, info: &Info) -> io::Result<()> {
+    let header = &info.header;
+    let ratio = |r: Ratio| format!("{}:{}", r.num, r.den);
+    writeln!(out, "file:        {}", info.name)?;
+    writeln!(out, "width:       {}", header.width())?;
+    writeln!(out, "height:      {}", header.height())?;
+    writeln!(out, "frame rate:  {}", ratio(header.framerate()))?;
+    writeln!(
+        out,
+        "interlace:   {} ({})",
+        header.interlace().tag(),
+        interlace_name(header.interlace())
+    )?;
+    writeln!(out, "aspect:      {}", ratio(header.aspect()))?;
+    writeln!(out, "colorspace:  {}", header.colorspace().tag())?;
+    writeln!(out, "bit depth:   {}", header.colorspace().bit_depth())?;
+    for x in header.extensions() {
+        writeln!(out, "extension:   X{}", x)?;
+    }
+    for w in &info.warnings {
+        writeln!(out, "warning:     {}", w)?;
+    }
+    writeln!(out, "header size: {}", info.header_size)?;
+    writeln!(out, "frame size:  {}", header.frame_size())?;
+    writeln!(out, "frames:      {}", info.frame_count)?;
+    writeln!(out, "duration:    {:.3} s", info.duration())?;
+    match info.file_size {
+        Some(size) => writeln!(out, "file size:   {}", size)?,
+        None => writeln!(out, "file size:   unknown")?,
+    }
+    writeln!(out, "stream size: {}", info.stream_size)?;
+    if let Some(ref e) = info.error {
+        writeln!(out, "error:       {}", e)?;
+    }
+    writeln!(
+        out,
+        "consistent:  {}",
+        if info.consistent() { "yes" } else { "no" }
+    )
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_list(items: &[String]) -> String {
+    let items: Vec<_> = items.iter().map(|s| json_string(s)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn write_json<W: Write>(out: &mut W, info: &Info) -> io::Result<()> {
+    let header = &info.header;
+    let ratio = |r: Ratio| format!("{{\"num\": {}, \"den\": {}}}", r.num, r.den);
+    let optional = |v: Option<String>| v.unwrap_or_else(|| "null".to_owned());
+    writeln!(out, "{{")?;
+    writeln!(out, "  \"file\": {},", json_string(&info.name))?;
+    writeln!(out, "  \"width\": {},", header.width())?;
+    writeln!(out, "  \"height\": {},", header.height())?;
+    writeln!(out, "  \"framerate\": {},", ratio(header.framerate()))?;
+    writeln!(
+        out,
+        "  \"interlace\": {},",
+        json_string(header.interlace().tag())
+    )?;
+    writeln!(out, "  \"aspect\": {},", ratio(header.aspect()))?;
+    writeln!(
+        out,
+        "  \"colorspace\": {},",
+        json_string(header.colorspace().tag())
+    )?;
+    writeln!(out, "  \"bit_depth\": {},", header.colorspace().bit_depth())?;
+    writeln!(out, "  \"extensions\": {},", json_list(header.extensions()))?;
+    writeln!(out, "  \"warnings\": {},", json_list(&info.warnings))?;
+    writeln!(out, "  \"header_size\": {},", info.header_size)?;
+    writeln!(out, "  \"frame_size\": {},", header.frame_size())?;
+    writeln!(out, "  \"frame_count\": {},", info.frame_count)?;
+    writeln!(out, "  \"duration\": {},", info.duration())?;
+    writeln!(
+        out,
+        "  \"file_size\": {},",
+        optional(info.file_size.map(|s| s.to_string()))
+    )?;
+    writeln!(out, "  \"stream_size\": {},", info.stream_size)?;
+    writeln!(
+        out,
+        "  \"error\": {},",
+        optional(info.error.as_deref().map(json_string))
+    )?;
+    writeln!(out, "  \"consistent\": {}", info.consistent())?;
+    writeln!(out, "}}")
+}
+
+fn usage() -> ! {
+    eprintln!("usage: y4minfo [--json] [FILE]");
+    process::exit(2);
+}
+
+fn main() {
+    let mut json = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => usage(),
+            _ if path.is_none() => path = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    let res = match path.as_deref() {
+        None | Some("-") => inspect(io::stdin().lock(), "-", None),
+        Some(path) => File::open(path).and_then(|file| {
+            let size = file.metadata()?.len();
+            inspect(BufReader::new(file), path, Some(size))
+        }),
+    };
+    let info = match res {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("y4minfo: {}: {}", path.as_deref().unwrap_or("-"), e);
+            process::exit(1);
+        }
+    };
+
+    let mut out = io::stdout().lock();
+    let res = if json {
+        write_json(&mut out, &info)
+    } else {
+        write_text(&mut out, &info)
+    };
+    if let Err(e) = res {
+        eprintln!("y4minfo: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Y4M: &[u8] = include_bytes!("../../assets/test.y4m");
+
+    #[test]
+    fn inspect_asset() {
+        let info = inspect(Y4M, "test.y4m", Some(Y4M.len() as u64)).unwrap();
+        assert_eq!(info.header_size, 34);
+        assert_eq!(info.frame_count, 51);
+        assert_eq!(info.stream_size, Y4M.len());
+        assert!(info.consistent());
+        assert!((info.duration() - 2.04).abs() < 1e-9);
+
+        let mut out = Vec::new();
+        write_json(&mut out, &info).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\"colorspace\": \"420jpeg\","));
+        assert!(out.contains("\"frame_size\": 165888,"));
+        assert!(out.contains("\"error\": null,"));
+    }
+
+    #[test]
+    fn inspect_truncated() {
+        let data = &Y4M[..Y4M.len() - 1];
+        let info = inspect(data, "-", None).unwrap();
+        assert_eq!(info.frame_count, 50);
+        assert!(info.error.is_some());
+        assert!(!info.consistent());
+
+        assert_eq!(json_string("a\"b\\\n\t"), "\"a\\\"b\\\\\\n\\u0009\"");
+    }
+}
@@ -0,0 +1,203 @@
+use crate::demuxer::Y4MHeader;
+use crate::pool::payload;
+use av_codec::decoder::Decoder;
+use av_codec::error::*;
+use av_data::frame::{ArcFrame, Frame, FrameBuffer, FrameError, FrameType, MediaKind, VideoInfo};
+use av_data::packet::Packet;
+use av_data::params::CodecParams;
+use av_data::pixel::Formaton;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Row alignment of the decoded planes
+const ALIGNMENT: usize = 32;
+
+/// Planes of a decoded frame
+///
+/// Samples wider than 8 bits take two bytes in little endian, matching the
+/// format reported by the decoder.
+pub struct Planes {
+    planes: Vec<Vec<u8>>,
+    linesizes: Vec<usize>,
+}
+
+impl FrameBuffer for Planes {
+    fn linesize(&self, idx: usize) -> std::result::Result<usize, FrameError> {
+        self.linesizes
+            .get(idx)
+            .copied()
+            .ok_or(FrameError::InvalidIndex)
+    }
+    fn count(&self) -> usize {
+        self.planes.len()
+    }
+    fn as_slice_inner(&self, idx: usize) -> std::result::Result<&[u8], FrameError> {
+        self.planes
+            .get(idx)
+            .map(|p| p.as_slice())
+            .ok_or(FrameError::InvalidIndex)
+    }
+    fn as_mut_slice_inner(&mut self, idx: usize) -> std::result::Result<&mut [u8], FrameError> {
+        self.planes
+            .get_mut(idx)
+            .map(|p| p.as_mut_slice())
+            .ok_or(FrameError::InvalidIndex)
+    }
+}
+
+/// Turns Y4M packets into frames
+pub struct Y4MDecoder {
+    header: Y4MHeader,
+    format: Arc<Formaton>,
+    frames: VecDeque<ArcFrame>,
+}
+
+impl Y4MDecoder {
+    /// Creates a decoder for the stream described by `params`
+    pub fn new(params: &CodecParams) -> Result<Y4MDecoder> {
+        let (header, format) =
+            Y4MHeader::from_codec_params(params).ok_or(Error::ConfigurationInvalid)?;
+        Ok(Y4MDecoder {
+            header,
+            format,
+            frames: VecDeque::new(),
+        })
+    }
+
+    fn decode(&self, pkt: &Packet) -> Result<Frame> {
+        let mut data = payload(pkt);
+        if data.len() != self.header.frame_size() {
+            return Err(Error::InvalidData);
+        }
+
+        let colorspace = self.header.colorspace();
+        let mut planes = Vec::with_capacity(colorspace.planes());
+        let mut linesizes = Vec::with_capacity(colorspace.planes());
+        for plane in 0..colorspace.planes() {
+            let (width, height) = self.header.plane_dimensions(plane);
+            let row = width * colorspace.bytes_per_sample();
+            let linesize = row.div_ceil(ALIGNMENT) * ALIGNMENT;
+
+            let mut dst = vec![0u8; linesize * height];
+            for (src, dst) in data[..row * height]
+                .chunks_exact(row.max(1))
+                .zip(dst.chunks_exact_mut(linesize.max(1)))
+            {
+                dst[..row].copy_from_slice(src);
+            }
+            data = &data[row * height..];
+
+            planes.push(dst);
+            linesizes.push(linesize);
+        }
+
+        let info = VideoInfo::new(
+            self.header.width(),
+            self.header.height(),
+            false,
+            FrameType::I,
+            self.format.clone(),
+        );
+
+        Ok(Frame {
+            kind: MediaKind::Video(info),
+            buf: Box::new(Planes { planes, linesizes }),
+            t: pkt.t.clone(),
+        })
+    }
+}
+
+impl Decoder for Y4MDecoder {
+    fn set_extradata(&mut self, _extra: &[u8]) {}
+
+    fn send_packet(&mut self, pkt: &Packet) -> Result<()> {
+        let frame = self.decode(pkt)?;
+        self.frames.push_back(Arc::new(frame));
+        Ok(())
+    }
+
+    fn receive_frame(&mut self) -> Result<ArcFrame> {
+        self.frames.pop_front().ok_or(Error::MoreDataNeeded)
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.frames.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::demuxer::Colorspace;
+    use av_data::params::{self, VideoInfo};
+
+    pub(crate) fn params(width: usize, height: usize, cs: Colorspace) -> CodecParams {
+        CodecParams {
+            extradata: None,
+            codec_id: None,
+            bit_rate: 0,
+            delay: 0,
+            convergence_window: 0,
+            kind: Some(params::MediaKind::Video(VideoInfo {
+                width,
+                height,
+                format: Some(Arc::new(cs.formaton())),
+            })),
+        }
+    }
+
+    #[test]
+    fn decode_420() {
+        let mut dec = Y4MDecoder::new(&params(3, 3, Colorspace::C420jpeg)).unwrap();
+        let mut pkt = Packet::with_capacity(17);
+        pkt.data.extend(0..17u8);
+        dec.send_packet(&pkt).unwrap();
+
+        let frame = dec.receive_frame().unwrap();
+        assert_eq!(frame.buf.count(), 3);
+        let y = frame.buf.as_slice_inner(0).unwrap();
+        let stride = frame.buf.linesize(0).unwrap();
+        assert_eq!(stride, ALIGNMENT);
+        assert_eq!(&y[..3], &[0, 1, 2]);
+        assert_eq!(&y[2 * stride..2 * stride + 3], &[6, 7, 8]);
+        let v = frame.buf.as_slice_inner(2).unwrap();
+        let stride = frame.buf.linesize(2).unwrap();
+        assert_eq!(&v[..2], &[13, 14]);
+        assert_eq!(&v[stride..stride + 2], &[15, 16]);
+
+        assert!(dec.receive_frame().is_err());
+    }
+
+    #[test]
+    fn decode_high_bit_depth() {
+        let mut dec = Y4MDecoder::new(&params(2, 1, Colorspace::C444p10)).unwrap();
+        let mut pkt = Packet::with_capacity(12);
+        pkt.data
+            .extend_from_slice(&[0xff, 0x03, 0x00, 0x02, 1, 0, 2, 0, 3, 0, 4, 0]);
+        dec.send_packet(&pkt).unwrap();
+
+        let frame = dec.receive_frame().unwrap();
+        let y = frame.buf.as_slice_inner(0).unwrap();
+        let y: Vec<u16> = y[..4]
+            .chunks_exact(2)
+            .map(|s| u16::from_le_bytes([s[0], s[1]]))
+            .collect();
+        assert_eq!(y, [0x3ff, 0x200]);
+    }
+
+    #[test]
+    fn reject_short_packet() {
+        let mut dec = Y4MDecoder::new(&params(4, 4, Colorspace::Cmono)).unwrap();
+        let mut pkt = Packet::with_capacity(15);
+        pkt.data.extend(0..15u8);
+        assert!(dec.send_packet(&pkt).is_err());
+
+        let huge = params(usize::MAX, 2, Colorspace::C444);
+        assert!(Y4MDecoder::new(&huge).is_err());
+    }
+}
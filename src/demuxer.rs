@@ -1,4 +1,12 @@
+use crate::error::Y4MError;
+use crate::forward::unsupported;
+use crate::pool::{FramePool, PooledFrame};
+use av_data::packet::Packet;
 use av_data::params::{CodecParams, MediaKind, VideoInfo};
+use av_data::pixel::{
+    ChromaLocation, Chromaton, ColorModel, Formaton, TrichromaticEncodingSystem, YUVRange,
+    YUVSystem,
+};
 use av_data::rational::Rational64;
 use av_format::buffer::Buffered;
 use av_format::common::GlobalInfo;
@@ -7,35 +15,892 @@ use av_format::demuxer::{Descr, Descriptor};
 use av_format::error::*;
 use av_format::stream::Stream;
 use nom::bytes::complete::tag;
-use nom::bytes::complete::take_till;
-use nom::combinator::map_res;
+use nom::bytes::complete::take_till1;
+use nom::bytes::streaming;
+use nom::character::complete::{char, digit1};
+use nom::combinator::{all_consuming, map, map_res};
+use nom::sequence::{preceded, separated_pair, terminated};
 use nom::{IResult, Offset};
 use std::collections::VecDeque;
-use std::io::SeekFrom;
+use std::fmt;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::sync::Arc;
 
+/// YUV4MPEG2 demuxer
+///
+/// [`Y4M_DESC`] creates a demuxer with the default settings, build one
+/// directly to tune it before handing it to an `av_format` context.
+///
+/// Each packet owns a copy of its frame data, unless the demuxer reads frames
+/// into a [`FramePool`] given to [`with_pool`](Self::with_pool).
 #[derive(Default)]
-struct Y4MDemuxer {
+pub struct Y4MDemuxer {
     header: Option<Y4MHeader>,
     queue: VecDeque<Event>,
+    pos: usize,
+    frame_num: u64,
+    input_len: Option<u64>,
+    frame_count: Option<u64>,
+    header_len: u64,
+    /// Offsets of the frames met so far, in order
+    index: Vec<u64>,
+    /// Whether frames carrying parameters, thus of varying length, were met
+    variable: bool,
+    limits: Limits,
+    mode: ParseMode,
+    /// Quirks met in the stream header, in lenient mode
+    warnings: Vec<Y4MError>,
+    /// Length of the data holding an incomplete header on the last attempt
+    partial_header_len: Option<usize>,
+    /// Whether seeking backward is impossible
+    forward_only: bool,
+    /// Buffers holding the packet payloads, if pooled
+    pool: Option<FramePool>,
+}
+
+/// How strictly the demuxer holds streams to the specification
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Rejects any deviation from the specification
+    Strict,
+    /// Accepts the quirks of known writers: `\r\n` line endings, extra
+    /// spaces, lowercase and duplicate tags
+    ///
+    /// The quirks met in the stream header are reported by
+    /// [`Y4MDemuxer::warnings`], along with a missing `C` tag, the ones in
+    /// frame headers are only logged.
+    #[default]
+    Lenient,
+}
+
+/// Bounds enforced by the demuxer on the streams it reads
+///
+/// Streams going past any of them are rejected with
+/// [`Y4MError::LimitExceeded`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub max_width: usize,
+    pub max_height: usize,
+    /// Maximum number of luma samples in a frame
+    pub max_pixels: usize,
+    /// Maximum length of the stream header, newline included
+    pub max_header_len: usize,
+    /// Maximum length of the parameters following each `FRAME` marker
+    pub max_frame_params_len: usize,
+    /// Maximum number of `X` tags in the stream header or in a frame header
+    pub max_extensions: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_width: 32768,
+            max_height: 32768,
+            max_pixels: 1 << 28,
+            max_header_len: 4096,
+            max_frame_params_len: 1024,
+            max_extensions: 64,
+        }
+    }
+}
+
+fn check_limit(
+    what: &'static str,
+    value: usize,
+    limit: usize,
+) -> std::result::Result<(), Y4MError> {
+    if value > limit {
+        Err(Y4MError::LimitExceeded { what, limit })
+    } else {
+        Ok(())
+    }
+}
+
+impl Limits {
+    pub(crate) fn check_header(
+        &self,
+        header: &Y4MHeader,
+        len: usize,
+    ) -> std::result::Result<(), Y4MError> {
+        check_limit("header length", len, self.max_header_len)?;
+        check_limit("width", header.width, self.max_width)?;
+        check_limit("height", header.height, self.max_height)?;
+        check_limit(
+            "pixel count",
+            header.width.saturating_mul(header.height),
+            self.max_pixels,
+        )?;
+        check_limit("X tag count", header.extensions.len(), self.max_extensions)
+    }
+
+    /// Longest frame marker allowed, newline included
+    pub(crate) fn max_frame_header_len(&self) -> usize {
+        self.max_frame_params_len.saturating_add(b"FRAME\n".len())
+    }
+
+    pub(crate) fn check_frame_params(
+        &self,
+        params: &FrameParams,
+        len: usize,
+    ) -> std::result::Result<(), Y4MError> {
+        check_limit(
+            "frame parameters length",
+            len.saturating_sub(b"FRAME\n".len()),
+            self.max_frame_params_len,
+        )?;
+        check_limit("X tag count", params.extensions.len(), self.max_extensions)
+    }
+}
+
+/// A ratio as carried by the `F` and `A` tags, `0:0` meaning unknown
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ratio {
+    pub num: usize,
+    pub den: usize,
+}
+
+impl Ratio {
+    pub fn new(num: usize, den: usize) -> Ratio {
+        Ratio { num, den }
+    }
+
+    /// Whether the ratio carries an actual value
+    pub fn is_known(&self) -> bool {
+        self.num != 0 && self.den != 0
+    }
+}
+
+/// Field order signalled by the `I` tag
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interlace {
+    /// `Ip`
+    #[default]
+    Progressive,
+    /// `It`
+    TopFieldFirst,
+    /// `Ib`
+    BottomFieldFirst,
+    /// `Im`, the field order is signalled per frame
+    Mixed,
+    /// `I?`
+    Unknown,
+}
+
+impl Interlace {
+    pub fn from_tag(tag: &str) -> Option<Interlace> {
+        match tag {
+            "p" => Some(Interlace::Progressive),
+            "t" => Some(Interlace::TopFieldFirst),
+            "b" => Some(Interlace::BottomFieldFirst),
+            "m" => Some(Interlace::Mixed),
+            "?" => Some(Interlace::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Interlace::Progressive => "p",
+            Interlace::TopFieldFirst => "t",
+            Interlace::BottomFieldFirst => "b",
+            Interlace::Mixed => "m",
+            Interlace::Unknown => "?",
+        }
+    }
+}
+
+/// Pixel layout signalled by the `C` tag
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colorspace {
+    #[default]
+    C420jpeg,
+    C420mpeg2,
+    C420paldv,
+    C411,
+    C422,
+    C444,
+    C444alpha,
+    Cmono,
+    Cmono9,
+    Cmono10,
+    Cmono12,
+    Cmono16,
+    C420p9,
+    C420p10,
+    C420p12,
+    C420p14,
+    C420p16,
+    C422p9,
+    C422p10,
+    C422p12,
+    C422p14,
+    C422p16,
+    C444p9,
+    C444p10,
+    C444p12,
+    C444p14,
+    C444p16,
+}
+
+impl Colorspace {
+    pub fn from_tag(tag: &str) -> Option<Colorspace> {
+        let cs = match tag {
+            "420" | "420jpeg" => Colorspace::C420jpeg,
+            "420mpeg2" => Colorspace::C420mpeg2,
+            "420paldv" => Colorspace::C420paldv,
+            "411" => Colorspace::C411,
+            "422" => Colorspace::C422,
+            "444" => Colorspace::C444,
+            "444alpha" => Colorspace::C444alpha,
+            "mono" => Colorspace::Cmono,
+            "mono9" => Colorspace::Cmono9,
+            "mono10" => Colorspace::Cmono10,
+            "mono12" => Colorspace::Cmono12,
+            "mono16" => Colorspace::Cmono16,
+            "420p9" => Colorspace::C420p9,
+            "420p10" => Colorspace::C420p10,
+            "420p12" => Colorspace::C420p12,
+            "420p14" => Colorspace::C420p14,
+            "420p16" => Colorspace::C420p16,
+            "422p9" => Colorspace::C422p9,
+            "422p10" => Colorspace::C422p10,
+            "422p12" => Colorspace::C422p12,
+            "422p14" => Colorspace::C422p14,
+            "422p16" => Colorspace::C422p16,
+            "444p9" => Colorspace::C444p9,
+            "444p10" => Colorspace::C444p10,
+            "444p12" => Colorspace::C444p12,
+            "444p14" => Colorspace::C444p14,
+            "444p16" => Colorspace::C444p16,
+            _ => return None,
+        };
+        Some(cs)
+    }
+
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Colorspace::C420jpeg => "420jpeg",
+            Colorspace::C420mpeg2 => "420mpeg2",
+            Colorspace::C420paldv => "420paldv",
+            Colorspace::C411 => "411",
+            Colorspace::C422 => "422",
+            Colorspace::C444 => "444",
+            Colorspace::C444alpha => "444alpha",
+            Colorspace::Cmono => "mono",
+            Colorspace::Cmono9 => "mono9",
+            Colorspace::Cmono10 => "mono10",
+            Colorspace::Cmono12 => "mono12",
+            Colorspace::Cmono16 => "mono16",
+            Colorspace::C420p9 => "420p9",
+            Colorspace::C420p10 => "420p10",
+            Colorspace::C420p12 => "420p12",
+            Colorspace::C420p14 => "420p14",
+            Colorspace::C420p16 => "420p16",
+            Colorspace::C422p9 => "422p9",
+            Colorspace::C422p10 => "422p10",
+            Colorspace::C422p12 => "422p12",
+            Colorspace::C422p14 => "422p14",
+            Colorspace::C422p16 => "422p16",
+            Colorspace::C444p9 => "444p9",
+            Colorspace::C444p10 => "444p10",
+            Colorspace::C444p12 => "444p12",
+            Colorspace::C444p14 => "444p14",
+            Colorspace::C444p16 => "444p16",
+        }
+    }
+
+    /// Bits per sample
+    pub fn bit_depth(&self) -> u8 {
+        match self {
+            Colorspace::Cmono9 | Colorspace::C420p9 | Colorspace::C422p9 | Colorspace::C444p9 => 9,
+            Colorspace::Cmono10
+            | Colorspace::C420p10
+            | Colorspace::C422p10
+            | Colorspace::C444p10 => 10,
+            Colorspace::Cmono12
+            | Colorspace::C420p12
+            | Colorspace::C422p12
+            | Colorspace::C444p12 => 12,
+            Colorspace::C420p14 | Colorspace::C422p14 | Colorspace::C444p14 => 14,
+            Colorspace::Cmono16
+            | Colorspace::C420p16
+            | Colorspace::C422p16
+            | Colorspace::C444p16 => 16,
+            _ => 8,
+        }
+    }
+
+    /// Bytes used to store a single sample, high bit depths use 16-bit little endian
+    pub fn bytes_per_sample(&self) -> usize {
+        if self.bit_depth() > 8 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Number of planes stored in each frame
+    pub fn planes(&self) -> usize {
+        match self {
+            Colorspace::Cmono
+            | Colorspace::Cmono9
+            | Colorspace::Cmono10
+            | Colorspace::Cmono12
+            | Colorspace::Cmono16 => 1,
+            Colorspace::C444alpha => 4,
+            _ => 3,
+        }
+    }
+
+    /// Horizontal and vertical chroma subsampling as log2 factors
+    pub fn chroma_shift(&self) -> (u8, u8) {
+        match self {
+            Colorspace::C420jpeg
+            | Colorspace::C420mpeg2
+            | Colorspace::C420paldv
+            | Colorspace::C420p9
+            | Colorspace::C420p10
+            | Colorspace::C420p12
+            | Colorspace::C420p14
+            | Colorspace::C420p16 => (1, 1),
+            Colorspace::C411 => (2, 0),
+            Colorspace::C422
+            | Colorspace::C422p9
+            | Colorspace::C422p10
+            | Colorspace::C422p12
+            | Colorspace::C422p14
+            | Colorspace::C422p16 => (1, 0),
+            _ => (0, 0),
+        }
+    }
+
+    /// Colorspace storing frames of the given pixel format, if any
+    pub fn from_formaton(fmt: &Formaton) -> Option<Colorspace> {
+        let depth = fmt.comp_info[0]?.depth;
+        let chroma = fmt.comp_info[1].map(|c| (c.h_ss, c.v_ss));
+        let cs = match (fmt.components, fmt.alpha, chroma, depth) {
+            (1, false, _, 8) => Colorspace::Cmono,
+            (1, false, _, 9) => Colorspace::Cmono9,
+            (1, false, _, 10) => Colorspace::Cmono10,
+            (1, false, _, 12) => Colorspace::Cmono12,
+            (1, false, _, 16) => Colorspace::Cmono16,
+            (3, false, Some((1, 1)), 8) => match fmt.chroma_location {
+                ChromaLocation::Left => Colorspace::C420mpeg2,
+                ChromaLocation::TopLeft => Colorspace::C420paldv,
+                _ => Colorspace::C420jpeg,
+            },
+            (3, false, Some((2, 0)), 8) => Colorspace::C411,
+            (3, false, Some((1, 0)), 8) => Colorspace::C422,
+            (3, false, Some((0, 0)), 8) => Colorspace::C444,
+            (4, true, Some((0, 0)), 8) => Colorspace::C444alpha,
+            (3, false, Some((1, 1)), 9) => Colorspace::C420p9,
+            (3, false, Some((1, 1)), 10) => Colorspace::C420p10,
+            (3, false, Some((1, 1)), 12) => Colorspace::C420p12,
+            (3, false, Some((1, 1)), 14) => Colorspace::C420p14,
+            (3, false, Some((1, 1)), 16) => Colorspace::C420p16,
+            (3, false, Some((1, 0)), 9) => Colorspace::C422p9,
+            (3, false, Some((1, 0)), 10) => Colorspace::C422p10,
+            (3, false, Some((1, 0)), 12) => Colorspace::C422p12,
+            (3, false, Some((1, 0)), 14) => Colorspace::C422p14,
+            (3, false, Some((1, 0)), 16) => Colorspace::C422p16,
+            (3, false, Some((0, 0)), 9) => Colorspace::C444p9,
+            (3, false, Some((0, 0)), 10) => Colorspace::C444p10,
+            (3, false, Some((0, 0)), 12) => Colorspace::C444p12,
+            (3, false, Some((0, 0)), 14) => Colorspace::C444p14,
+            (3, false, Some((0, 0)), 16) => Colorspace::C444p16,
+            _ => return None,
+        };
+        Some(cs)
+    }
+
+    /// Pixel format describing the planes of a frame
+    pub fn formaton(&self) -> Formaton {
+        let (h_ss, v_ss) = self.chroma_shift();
+        let depth = self.bit_depth();
+        let chromaton = |h_ss, v_ss, comp_offs| {
+            if depth > 8 {
+                Chromaton::yuvhb(h_ss, v_ss, depth, comp_offs)
+            } else {
+                Chromaton::yuv8(h_ss, v_ss, comp_offs)
+            }
+        };
+        let components = [
+            chromaton(0, 0, 0),
+            chromaton(h_ss, v_ss, 1),
+            chromaton(h_ss, v_ss, 2),
+            chromaton(0, 0, 3),
+        ];
+        let model = ColorModel::Trichromatic(TrichromaticEncodingSystem::YUV(YUVSystem::YCbCr(
+            YUVRange::Limited,
+        )));
+
+        let mut fmt = Formaton::new(
+            model,
+            &components[..self.planes()],
+            0,
+            false,
+            *self == Colorspace::C444alpha,
+            false,
+        );
+        fmt.chroma_location = match self {
+            Colorspace::C420jpeg => ChromaLocation::Center,
+            Colorspace::C420mpeg2 => ChromaLocation::Left,
+            Colorspace::C420paldv => ChromaLocation::TopLeft,
+            _ => ChromaLocation::Unspecified,
+        };
+        fmt
+    }
+}
+
+/// Parameters following a `FRAME` marker
+///
+/// The demuxer attaches them to the packets as `t.user_private`, the muxer
+/// writes the ones found there.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct FrameParams {
+    interlace: Option<Interlace>,
+    extensions: Vec<String>,
+}
+
+impl FrameParams {
+    pub fn new() -> FrameParams {
+        Default::default()
+    }
+
+    /// Overrides the interlacing signalled in the stream header
+    pub fn with_interlace(mut self, interlace: Interlace) -> FrameParams {
+        self.interlace = Some(interlace);
+        self
+    }
+
+    /// Adds an `X` tag
+    pub fn with_extension<S: Into<String>>(mut self, extension: S) -> FrameParams {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    /// Parameters carried by a packet, if any
+    pub fn from_packet(pkt: &Packet) -> Option<&FrameParams> {
+        let private = pkt.t.user_private.as_ref()?;
+        match private.downcast_ref::<PooledFrame>() {
+            Some(frame) => Some(frame.params()).filter(|params| !params.is_empty()),
+            None => private.downcast_ref(),
+        }
+    }
+
+    pub fn interlace(&self) -> Option<Interlace> {
+        self.interlace
+    }
+
+    /// Values of the `X` tags, without the leading `X`
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.interlace.is_none() && self.extensions.is_empty()
+    }
+}
+
+/// Ratio terms are read back as 32 bits integers
+fn check_ratio(ratio: Ratio) -> std::result::Result<(), &'static str> {
+    if u32::try_from(ratio.num).is_err() || u32::try_from(ratio.den).is_err() {
+        return Err("ratio terms do not fit 32 bits");
+    }
+    Ok(())
+}
+
+/// `X` values end at the first space or newline
+pub(crate) fn check_extension(x: &str) -> std::result::Result<(), &'static str> {
+    if x.contains([' ', '\n', '\r']) {
+        return Err("X tags cannot contain spaces or newlines");
+    }
+    Ok(())
+}
+
+/// Formats the parameters as written after `FRAME`, each preceded by a space
+impl fmt::Display for FrameParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(interlace) = self.interlace {
+            write!(f, " I{}", interlace.tag())?;
+        }
+        for x in &self.extensions {
+            write!(f, " X{}", x)?;
+        }
+        Ok(())
+    }
+}
+
+/// Extension marking full range samples
+pub(crate) const FULL_RANGE: &str = "COLORRANGE=FULL";
+
+/// Whether the pixel format uses the full range of sample values
+pub(crate) fn is_full_range(fmt: &Formaton) -> bool {
+    matches!(
+        fmt.model,
+        ColorModel::Trichromatic(TrichromaticEncodingSystem::YUV(YUVSystem::YCbCr(
+            YUVRange::Full
+        )))
+    )
 }
 
 #[derive(Default, Clone, Debug)]
 pub struct Y4MHeader {
-    width: usize,
-    height: usize,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) framerate: Ratio,
+    pub(crate) interlace: Interlace,
+    pub(crate) aspect: Ratio,
+    pub(crate) colorspace: Colorspace,
+    pub(crate) extensions: Vec<String>,
+}
+
+/// Formats the header as it is written in a stream, without the trailing newline
+impl fmt::Display for Y4MHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "YUV4MPEG2 W{} H{} F{}:{} I{} A{}:{} C{}",
+            self.width,
+            self.height,
+            self.framerate.num,
+            self.framerate.den,
+            self.interlace.tag(),
+            self.aspect.num,
+            self.aspect.den,
+            self.colorspace.tag()
+        )?;
+        for x in &self.extensions {
+            write!(f, " X{}", x)?;
+        }
+        Ok(())
+    }
+}
+
+impl Y4MHeader {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Frames per second, as `num:den`
+    pub fn framerate(&self) -> Ratio {
+        self.framerate
+    }
+
+    pub fn interlace(&self) -> Interlace {
+        self.interlace
+    }
+
+    /// Pixel aspect ratio
+    pub fn aspect(&self) -> Ratio {
+        self.aspect
+    }
+
+    pub fn colorspace(&self) -> Colorspace {
+        self.colorspace
+    }
+
+    /// Values of the `X` tags, without the leading `X`
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// Time base of the stream, ticking once per frame
+    ///
+    /// Streams lacking a valid `F` tag are assumed to run at 25 fps.
+    pub fn timebase(&self) -> Rational64 {
+        let fps = if self.framerate.is_known() {
+            self.framerate
+        } else {
+            Ratio::new(25, 1)
+        };
+        Rational64::new(fps.den as i64, fps.num as i64)
+    }
+
+    /// Pixel format of the stream, honouring the `XCOLORRANGE` extension
+    pub fn formaton(&self) -> Formaton {
+        let mut fmt = self.colorspace.formaton();
+        if self.extensions.iter().any(|x| x == FULL_RANGE) {
+            fmt.model = ColorModel::Trichromatic(TrichromaticEncodingSystem::YUV(
+                YUVSystem::YCbCr(YUVRange::Full),
+            ));
+        }
+        fmt
+    }
+
+    /// Width and height in samples of the given plane
+    pub fn plane_dimensions(&self, plane: usize) -> (usize, usize) {
+        let ceil_shift =
+            |v: usize, shift: u8| (v >> shift) + (v & ((1 << shift) - 1) != 0) as usize;
+        match plane {
+            1 | 2 => {
+                let (h_ss, v_ss) = self.colorspace.chroma_shift();
+                (ceil_shift(self.width, h_ss), ceil_shift(self.height, v_ss))
+            }
+            _ => (self.width, self.height),
+        }
+    }
+
+    /// Size in bytes of the given plane
+    pub fn plane_size(&self, plane: usize) -> usize {
+        let (w, h) = self.plane_dimensions(plane);
+        w * h * self.colorspace.bytes_per_sample()
+    }
+
+    /// Size in bytes of the plane data following each `FRAME` marker
+    pub fn frame_size(&self) -> usize {
+        (0..self.colorspace.planes())
+            .map(|plane| self.plane_size(plane))
+            .sum()
+    }
+
+    /// Checks that the header can be written and read back
+    pub(crate) fn check(&self) -> std::result::Result<(), &'static str> {
+        if self.width == 0 || self.height == 0 {
+            return Err("dimensions cannot be zero");
+        }
+        if self.checked_frame_size().is_none() {
+            return Err("frame size overflows");
+        }
+        check_ratio(self.framerate)?;
+        check_ratio(self.aspect)?;
+        for x in &self.extensions {
+            check_extension(x)?;
+        }
+        Ok(())
+    }
+
+    /// Same as [`frame_size`](Self::frame_size), `None` if it overflows
+    pub fn checked_frame_size(&self) -> Option<usize> {
+        (0..self.colorspace.planes()).try_fold(0usize, |size, plane| {
+            let (w, h) = self.plane_dimensions(plane);
+            let plane_size = w
+                .checked_mul(h)?
+                .checked_mul(self.colorspace.bytes_per_sample())?;
+            size.checked_add(plane_size)
+        })
+    }
+
+    /// Header of the pictures described by codec parameters, along with their
+    /// pixel format
+    ///
+    /// `None` if the parameters are not for video, the format has no matching
+    /// colorspace or the frame size overflows.
+    pub(crate) fn from_codec_params(params: &CodecParams) -> Option<(Y4MHeader, Arc<Formaton>)> {
+        let video = match params.kind {
+            Some(MediaKind::Video(ref video)) => video,
+            _ => return None,
+        };
+        let format = video
+            .format
+            .clone()
+            .unwrap_or_else(|| Arc::new(Colorspace::default().formaton()));
+        let colorspace = Colorspace::from_formaton(&format)?;
+        let header = Y4MHeader {
+            width: video.width,
+            height: video.height,
+            colorspace,
+            ..Default::default()
+        };
+        if header.checked_frame_size().is_none() {
+            error!(
+                "frame size of a {}x{} picture overflows",
+                video.width, video.height
+            );
+            return None;
+        }
+        Some((header, format))
+    }
 }
 
 impl Y4MDemuxer {
     pub fn new() -> Y4MDemuxer {
         Default::default()
     }
+
+    /// Sets the total length in bytes of the input
+    ///
+    /// Since every frame has the same size, it lets the demuxer report the
+    /// number of frames and the duration of the stream. The demuxer has no
+    /// other way to know the length, see [`Y4M_DESC`].
+    pub fn with_input_len(mut self, len: u64) -> Y4MDemuxer {
+        self.input_len = Some(len);
+        self
+    }
+
+    /// Sets the bounds enforced on the stream
+    pub fn with_limits(mut self, limits: Limits) -> Y4MDemuxer {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets how strictly the stream is parsed
+    pub fn with_mode(mut self, mode: ParseMode) -> Y4MDemuxer {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets whether the input can be seeked, such as a file, or only read
+    /// forward, such as a pipe
+    ///
+    /// Seeking a demuxer over a forward-only input fails with an
+    /// `Unsupported` error, leaving the input and the demuxer untouched.
+    pub fn with_seekable(mut self, seekable: bool) -> Y4MDemuxer {
+        self.forward_only = !seekable;
+        self
+    }
+
+    /// Reads frames into buffers of `pool`
+    ///
+    /// Packets then carry a [`PooledFrame`] in `t.user_private` instead of
+    /// `data`, see [`PooledFrame::from_packet`]. Cloning it shares the frame,
+    /// and its buffer is reused once the packet and all the clones are dropped.
+    pub fn with_pool(mut self, pool: FramePool) -> Y4MDemuxer {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Quirks accepted while reading the stream header
+    ///
+    /// Always empty in strict mode, since quirks are errors there.
+    pub fn warnings(&self) -> &[Y4MError] {
+        &self.warnings
+    }
+
+    /// Number of frames in the stream, if the input length is known
+    pub fn frame_count(&self) -> Option<u64> {
+        self.frame_count
+    }
+
+    /// Computes where `frame` starts and rewinds the demuxer to it
+    ///
+    /// The input has to be moved to the returned position before calling
+    /// `read_event` again, the next packet is then `frame`.
+    ///
+    /// Frame offsets are derived from the fixed frame size and checked against
+    /// `input`. If frames carry parameters, `input` is scanned and indexed from
+    /// the last known frame up to `frame` instead.
+    pub fn seek_frame<R: Read + Seek + ?Sized>(
+        &mut self,
+        input: &mut R,
+        frame: u64,
+    ) -> Result<SeekFrom> {
+        if self.forward_only {
+            return Err(Error::Io(unsupported()));
+        }
+        let header = self.header.as_ref().ok_or(Error::InvalidData)?;
+        let frame_len = frame_len(header);
+
+        let pos = match self.index.get(frame as usize) {
+            Some(&pos) => pos,
+            None if !self.variable => {
+                let pos = frame
+                    .checked_mul(frame_len)
+                    .and_then(|pos| pos.checked_add(self.header_len))
+                    .ok_or(Error::InvalidData)?;
+                match self.frame_count {
+                    Some(count) if frame > count => return Err(Error::InvalidData),
+                    Some(count) if frame == count => pos,
+                    _ if is_frame_start(input, pos)? => pos,
+                    _ => {
+                        debug!("frame {} is not at offset {}, scanning", frame, pos);
+                        self.variable = true;
+                        self.scan_to(input, frame)?
+                    }
+                }
+            }
+            None => self.scan_to(input, frame)?,
+        };
+
+        self.queue.clear();
+        self.pos = pos as usize;
+        self.frame_num = frame;
+
+        Ok(SeekFrom::Start(pos))
+    }
+
+    /// Same as [`seek_frame`](Self::seek_frame), with `ts` expressed in the
+    /// stream timebase
+    pub fn seek_timestamp<R: Read + Seek + ?Sized>(
+        &mut self,
+        input: &mut R,
+        ts: i64,
+    ) -> Result<SeekFrom> {
+        if ts < 0 {
+            return Err(Error::InvalidData);
+        }
+        // The timebase ticks once per frame
+        self.seek_frame(input, ts as u64)
+    }
+
+    /// Walks `input` from the last indexed frame, indexing every frame met
+    /// until `frame` is reached
+    fn scan_to<R: Read + Seek + ?Sized>(&mut self, input: &mut R, frame: u64) -> Result<u64> {
+        let frame_size = self.header.as_ref().ok_or(Error::InvalidData)?.frame_size() as u64;
+        let mut pos = match self.index.last() {
+            Some(&last) => {
+                input.seek(SeekFrom::Start(last))?;
+                let len =
+                    read_frame_header(input, &self.limits, self.mode)?.ok_or(Error::InvalidData)?;
+                next_frame(last, len, frame_size)?
+            }
+            None => self.header_len,
+        };
+
+        while (self.index.len() as u64) < frame {
+            input.seek(SeekFrom::Start(pos))?;
+            let len =
+                read_frame_header(input, &self.limits, self.mode)?.ok_or(Error::InvalidData)?;
+            self.index.push(pos);
+            pos = next_frame(pos, len, frame_size)?;
+        }
+
+        // `frame` may be one past the last frame, to seek to the end
+        input.seek(SeekFrom::Start(pos))?;
+        if read_frame_header(input, &self.limits, self.mode)?.is_some() {
+            self.index.push(pos);
+        }
+
+        Ok(pos)
+    }
+
+    fn count_frames(&self, header: &Y4MHeader, header_len: usize) -> Option<u64> {
+        let payload = self.input_len?.checked_sub(header_len as u64)?;
+        let frame_len = frame_len(header);
+        if payload % frame_len != 0 {
+            warn!(
+                "{} bytes of frame data are not a multiple of the {} bytes frame length",
+                payload, frame_len
+            );
+            return None;
+        }
+        Some(payload / frame_len)
+    }
 }
 
 impl Demuxer for Y4MDemuxer {
     fn read_headers(&mut self, buf: &Box<dyn Buffered>, info: &mut GlobalInfo) -> Result<SeekFrom> {
-        match header(buf.data()) {
+        let mut warnings = Vec::new();
+        match header(buf.data(), self.mode, &mut warnings) {
             Ok((input, header)) => {
                 debug!("found header: {:?}", header);
+                for warning in &warnings {
+                    warn!("accepting quirky header: {}", warning);
+                }
+                self.warnings = warnings;
+                if let Err(e) = self.limits.check_header(&header, buf.data().offset(input)) {
+                    error!("error reading headers: {}", e);
+                    return Err(e.into());
+                }
+                self.pos = buf.data().offset(input);
+                self.header_len = self.pos as u64;
+                self.frame_count = self.count_frames(&header, self.pos);
+                // The timebase ticks once per frame
+                let duration = self.frame_count;
                 let st = Stream {
                     id: 0,
                     index: 0,
@@ -48,21 +913,42 @@ impl Demuxer for Y4MDemuxer {
                         kind: Some(MediaKind::Video(VideoInfo {
                             width: header.width,
                             height: header.height,
-                            format: None,
+                            format: Some(Arc::new(header.formaton())),
                         })),
                     },
-                    start: None,
-                    duration: None,
-                    timebase: Rational64::new(1, 1000 * 1000 * 1000),
+                    start: Some(0),
+                    duration,
+                    timebase: header.timebase(),
                     user_private: None,
                 };
+                if duration.is_some() {
+                    info.duration = duration;
+                    info.timebase = Some(header.timebase());
+                }
                 self.header = Some(header);
                 info.add_stream(st);
-                Ok(SeekFrom::Current(buf.data().offset(input) as i64))
+                Ok(SeekFrom::Current(self.pos as i64))
             }
-            Err(e) => {
-                error!("error reading headers: {:?}", e);
-                Err(Error::InvalidData)
+            Err(nom::Err::Incomplete(_)) => {
+                let len = buf.data().len();
+                if let Err(e) = check_limit("header length", len, self.limits.max_header_len) {
+                    error!("error reading headers: {}", e);
+                    return Err(e.into());
+                }
+                // The context keeps asking for more data, even at the end of
+                // the input
+                if self.partial_header_len == Some(len) {
+                    let e = Y4MError::TruncatedHeader { offset: len };
+                    error!("error reading headers: {}", e);
+                    return Err(e.into());
+                }
+                self.partial_header_len = Some(len);
+                debug!("incomplete header in {} bytes", len);
+                Err(Error::MoreDataNeeded(len + 1))
+            }
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                error!("error reading headers: {}", e);
+                Err(e.into())
             }
         }
     }
@@ -76,8 +962,82 @@ impl Demuxer for Y4MDemuxer {
                 return Ok((SeekFrom::Current(0), Event::Eof));
             }
 
-            // TODO implement
-            Err(Error::InvalidData)
+            let header = self.header.as_ref().ok_or(Error::InvalidData)?;
+            let data = buf.data();
+            let (input, params) = match frame_header(data, self.mode) {
+                Ok(res) => res,
+                Err(nom::Err::Incomplete(_)) if data.len() > self.limits.max_frame_header_len() => {
+                    return Err(Y4MError::LimitExceeded {
+                        what: "frame parameters length",
+                        limit: self.limits.max_frame_params_len,
+                    }
+                    .into());
+                }
+                Err(nom::Err::Incomplete(needed)) => {
+                    let needed = match needed {
+                        nom::Needed::Size(sz) => sz.get(),
+                        nom::Needed::Unknown => 1,
+                    };
+                    return Ok((
+                        SeekFrom::Current(0),
+                        Event::MoreDataNeeded(data.len() + needed),
+                    ));
+                }
+                Err(e) => {
+                    error!("error reading frame header: {:?}", e);
+                    return Err(Y4MError::InvalidFrameHeader { offset: self.pos }.into());
+                }
+            };
+
+            let header_len = data.offset(input);
+            self.limits.check_frame_params(&params, header_len)?;
+            let frame_size = header.frame_size();
+            if input.len() < frame_size {
+                return Ok((
+                    SeekFrom::Current(0),
+                    Event::MoreDataNeeded(header_len.saturating_add(frame_size)),
+                ));
+            }
+
+            if !params.is_empty() {
+                self.variable = true;
+            }
+            let mut pkt = Packet::new();
+            match self.pool {
+                Some(ref pool) => {
+                    let mut buffer = pool.get(frame_size);
+                    buffer.data.copy_from_slice(&input[..frame_size]);
+                    pkt.t.user_private = Some(Arc::new(PooledFrame {
+                        width: header.width,
+                        height: header.height,
+                        colorspace: header.colorspace,
+                        params,
+                        buffer: Arc::new(buffer),
+                    }));
+                }
+                None => {
+                    pkt.data.extend_from_slice(&input[..frame_size]);
+                    if !params.is_empty() {
+                        pkt.t.user_private = Some(Arc::new(params));
+                    }
+                }
+            }
+            pkt.pos = Some(self.pos);
+            pkt.stream_index = 0;
+            pkt.is_key = true;
+            pkt.t.pts = Some(self.frame_num as i64);
+            pkt.t.dts = Some(self.frame_num as i64);
+            pkt.t.duration = Some(1);
+            pkt.t.timebase = Some(header.timebase());
+
+            if self.index.len() as u64 == self.frame_num {
+                self.index.push(self.pos as u64);
+            }
+            let consumed = header_len + frame_size;
+            self.pos += consumed;
+            self.frame_num += 1;
+
+            Ok((SeekFrom::Current(consumed as i64), Event::NewPacket(pkt)))
         }
     }
 }
@@ -86,31 +1046,309 @@ fn from_utf8(input: &[u8]) -> std::result::Result<&str, std::str::Utf8Error> {
     std::str::from_utf8(input)
 }
 
-fn header_token(input: &[u8]) -> IResult<&[u8], &str> {
-    map_res(take_till(|c| c == b' '), from_utf8)(input).map(|(i, token_str)| {
-        // Remove space from input
-        (&i[1..], token_str)
+fn header_token(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    preceded(
+        tag(" "),
+        take_till1(|c| c == b' ' || c == b'\n' || c == b'\r'),
+    )(input)
+}
+
+/// Fails with `quirk` in strict mode, records it as a warning otherwise
+fn quirk(
+    mode: ParseMode,
+    warnings: &mut Vec<Y4MError>,
+    quirk: Y4MError,
+) -> std::result::Result<(), Y4MError> {
+    match mode {
+        ParseMode::Strict => Err(quirk),
+        ParseMode::Lenient => {
+            warnings.push(quirk);
+            Ok(())
+        }
+    }
+}
+
+fn number(input: &str) -> IResult<&str, usize> {
+    map_res(digit1, str::parse)(input)
+}
+
+fn dimension(input: &str) -> Option<usize> {
+    all_consuming(number)(input)
+        .ok()
+        .map(|(_, n)| n)
+        .filter(|&n| n > 0)
+}
+
+/// Ratio terms are kept within 32 bits so that they always fit a `Rational64`
+fn ratio_term(input: &str) -> IResult<&str, usize> {
+    map(map_res(digit1, str::parse::<u32>), |n| n as usize)(input)
+}
+
+fn ratio(input: &str) -> IResult<&str, Ratio> {
+    map(
+        separated_pair(ratio_term, char(':'), ratio_term),
+        |(num, den)| Ratio::new(num, den),
+    )(input)
+}
+
+/// Parses a ratio written as `num:den`, as in the `F` and `A` tags
+pub(crate) fn parse_ratio(input: &str) -> Option<Ratio> {
+    all_consuming(ratio)(input).ok().map(|(_, ratio)| ratio)
+}
+
+const MAGIC: &[u8] = b"YUV4MPEG2";
+
+/// Parses a stream header, reporting a header cut short by the end of `input`
+/// as incomplete so that it can be retried once more data is available
+fn header<'a>(
+    input: &'a [u8],
+    mode: ParseMode,
+    warnings: &mut Vec<Y4MError>,
+) -> IResult<&'a [u8], Y4MHeader, Y4MError> {
+    parse_header(input, mode, warnings).map_err(|e| match e {
+        Y4MError::TruncatedHeader { .. } => nom::Err::Incomplete(nom::Needed::Unknown),
+        e => nom::Err::Failure(e),
     })
 }
 
-fn header(input: &[u8]) -> IResult<&[u8], Y4MHeader> {
+/// Parses a stream header, reporting a header cut short by the end of `input`
+/// as truncated
+///
+/// In lenient mode, the quirks accepted are pushed to `warnings`.
+pub(crate) fn parse_header<'a>(
+    input: &'a [u8],
+    mode: ParseMode,
+    warnings: &mut Vec<Y4MError>,
+) -> std::result::Result<(&'a [u8], Y4MHeader), Y4MError> {
     let mut header = Y4MHeader::default();
-    let (mut i, _) = tag("YUV4MPEG2 ")(input)?;
+    let mut width = None;
+    let mut height = None;
+    let mut seen = Vec::new();
+
+    let mut i = match streaming::tag::<_, _, nom::error::Error<_>>(MAGIC)(input) {
+        Ok((i, _)) => i,
+        Err(nom::Err::Incomplete(_)) => {
+            return Err(Y4MError::TruncatedHeader {
+                offset: input.len(),
+            })
+        }
+        Err(_) => return Err(Y4MError::MissingMagic { offset: 0 }),
+    };
 
     loop {
-        let (ii, token) = header_token(i)?;
-        let (id, val) = token.split_at(1);
-        i = ii;
+        let offset = input.offset(i);
+        if let Some(ii) = i.strip_prefix(b"\n") {
+            i = ii;
+            break;
+        }
+        if let Some(ii) = i.strip_prefix(b"\r\n") {
+            quirk(mode, warnings, Y4MError::CarriageReturn { offset })?;
+            i = ii;
+            break;
+        }
+
+        if i.is_empty() || i == b" " || i == b"\r" {
+            return Err(Y4MError::TruncatedHeader {
+                offset: input.len(),
+            });
+        }
+        if i[0] == b' ' && matches!(i[1], b' ' | b'\n' | b'\r') {
+            quirk(mode, warnings, Y4MError::UnexpectedSpace { offset })?;
+            i = &i[1..];
+            continue;
+        }
+        let (ii, token) = header_token(i).map_err(|_| Y4MError::MalformedTag { offset })?;
+        if ii.is_empty() {
+            return Err(Y4MError::TruncatedHeader {
+                offset: input.len(),
+            });
+        }
+
+        // Skip the separating space
+        let offset = offset + 1;
+        let token = from_utf8(token).map_err(|e| Y4MError::InvalidUtf8 {
+            offset: offset + e.valid_up_to(),
+        })?;
+        let mut chars = token.chars();
+        let mut id = chars.next();
+        let val = chars.as_str();
+        if let Some(c) = id.filter(char::is_ascii_lowercase) {
+            quirk(mode, warnings, Y4MError::LowercaseTag { offset })?;
+            id = Some(c.to_ascii_uppercase());
+        }
+        if let Some(c) = id.filter(|c| "WHFIAC".contains(*c)) {
+            if seen.contains(&c) {
+                // The last occurrence wins
+                quirk(mode, warnings, Y4MError::DuplicateTag { offset, tag: c })?;
+            }
+            seen.push(c);
+        }
         match id {
-            "W" => header.width = val.parse::<usize>().unwrap_or(0),
-            "H" => header.height = val.parse::<usize>().unwrap_or(0),
-            _ => break,
+            Some('W') => width = Some(dimension(val).ok_or(Y4MError::InvalidDimension { offset })?),
+            Some('H') => {
+                height = Some(dimension(val).ok_or(Y4MError::InvalidDimension { offset })?)
+            }
+            Some('F') => {
+                header.framerate = all_consuming(ratio)(val)
+                    .map_err(|_| Y4MError::MalformedRatio { offset })?
+                    .1
+            }
+            Some('A') => {
+                header.aspect = all_consuming(ratio)(val)
+                    .map_err(|_| Y4MError::MalformedRatio { offset })?
+                    .1
+            }
+            Some('I') => {
+                header.interlace =
+                    Interlace::from_tag(val).ok_or(Y4MError::InvalidInterlace { offset })?
+            }
+            Some('C') => {
+                header.colorspace =
+                    Colorspace::from_tag(val).ok_or_else(|| Y4MError::UnknownColorspace {
+                        offset,
+                        value: val.to_owned(),
+                    })?
+            }
+            Some('X') => header.extensions.push(val.to_owned()),
+            _ => {
+                warn!("skipping unknown header tag {}", token);
+            }
         }
+        i = ii;
+    }
+
+    let offset = input.offset(i);
+    header.width = width.ok_or(Y4MError::MissingWidth { offset })?;
+    header.height = height.ok_or(Y4MError::MissingHeight { offset })?;
+    // The specification defaults to 420jpeg, yet some readers require the tag
+    if !seen.contains(&'C') && mode == ParseMode::Lenient {
+        warnings.push(Y4MError::MissingColorspace { offset });
+    }
+    if header.checked_frame_size().is_none() {
+        return Err(Y4MError::FrameSizeOverflow {
+            width: header.width,
+            height: header.height,
+        });
     }
 
     Ok((i, header))
 }
 
+fn frame_params(mut i: &[u8], mode: ParseMode) -> IResult<&[u8], FrameParams> {
+    let mut params = FrameParams::default();
+
+    while !i.is_empty() {
+        if mode == ParseMode::Lenient && (i == b" " || i.starts_with(b"  ")) {
+            warn!("skipping extra space in frame header");
+            i = &i[1..];
+            continue;
+        }
+        let (ii, token) = map_res(header_token, from_utf8)(i)?;
+        let invalid = || {
+            nom::Err::Failure(nom::error::Error::new(
+                &i[1..],
+                nom::error::ErrorKind::Verify,
+            ))
+        };
+        let mut chars = token.chars();
+        let id = chars.next().ok_or_else(invalid)?;
+        let val = chars.as_str();
+        match id {
+            'I' => params.interlace = Some(Interlace::from_tag(val).ok_or_else(invalid)?),
+            'X' => params.extensions.push(val.to_owned()),
+            _ => {
+                warn!("skipping unknown frame tag {}", token);
+            }
+        }
+        i = ii;
+    }
+
+    Ok((i, params))
+}
+
+/// Parses a `FRAME` marker, its optional parameters and the terminating newline
+pub(crate) fn frame_header(input: &[u8], mode: ParseMode) -> IResult<&[u8], FrameParams> {
+    let (i, mut params) = terminated(
+        preceded(
+            streaming::tag("FRAME"),
+            streaming::take_till(|c| c == b'\n'),
+        ),
+        streaming::tag("\n"),
+    )(input)?;
+    if mode == ParseMode::Lenient {
+        if let Some(stripped) = params.strip_suffix(b"\r") {
+            warn!("accepting \\r\\n line ending in frame header");
+            params = stripped;
+        }
+    }
+    let (_, params) = all_consuming(|i| frame_params(i, mode))(params)?;
+
+    Ok((i, params))
+}
+
+/// Length of a frame without parameters, marker included
+fn frame_len(header: &Y4MHeader) -> u64 {
+    (header.frame_size() as u64).saturating_add(b"FRAME\n".len() as u64)
+}
+
+/// Offset of the frame following the one at `pos`
+fn next_frame(pos: u64, header_len: u64, frame_size: u64) -> Result<u64> {
+    pos.checked_add(header_len)
+        .and_then(|pos| pos.checked_add(frame_size))
+        .ok_or(Error::InvalidData)
+}
+
+/// Checks whether a parameterless frame marker sits at `pos`
+fn is_frame_start<R: Read + Seek + ?Sized>(input: &mut R, pos: u64) -> Result<bool> {
+    let mut marker = [0u8; 6];
+    input.seek(SeekFrom::Start(pos))?;
+    match input.read_exact(&mut marker) {
+        Ok(()) => Ok(&marker == b"FRAME\n"),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reads a frame marker from `input`, returning its length or `None` at the
+/// end of the input
+fn read_frame_header<R: Read + ?Sized>(
+    input: &mut R,
+    limits: &Limits,
+    mode: ParseMode,
+) -> Result<Option<u64>> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        if input.read(&mut byte)? == 0 {
+            if line.is_empty() {
+                return Ok(None);
+            }
+            return Err(Error::InvalidData);
+        }
+        line.push(byte[0]);
+        if line.len() > limits.max_frame_header_len() {
+            return Err(Y4MError::LimitExceeded {
+                what: "frame parameters length",
+                limit: limits.max_frame_params_len,
+            }
+            .into());
+        }
+        match frame_header(&line, mode) {
+            Ok((_, params)) => {
+                limits.check_frame_params(&params, line.len())?;
+                return Ok(Some(line.len() as u64));
+            }
+            Err(nom::Err::Incomplete(_)) => continue,
+            Err(_) => return Err(Error::InvalidData),
+        }
+    }
+}
+
+/// Probe score of data starting with a complete and valid header
+const PROBE_SCORE_HEADER: u8 = 100;
+/// Probe score of data starting with the magic and a header cut short
+const PROBE_SCORE_MAGIC: u8 = 25;
+
 struct Des {
     d: Descr,
 }
@@ -123,14 +1361,20 @@ impl Descriptor for Des {
         &self.d
     }
     fn probe(&self, data: &[u8]) -> u8 {
-        match header(&data[..=10]) {
-            Ok(_) => 10,
+        match parse_header(data, ParseMode::Lenient, &mut Vec::new()) {
+            Ok(_) => PROBE_SCORE_HEADER,
+            Err(Y4MError::TruncatedHeader { .. }) if data.starts_with(MAGIC) => PROBE_SCORE_MAGIC,
             _ => 0,
         }
     }
 }
 
 /// used by av context
+///
+/// The demuxers it creates cannot learn the length of their input, as
+/// `av_format` only lends them its buffered data, so they report neither the
+/// frame count nor the duration. Build a [`Y4MDemuxer`] with
+/// [`with_input_len`](Y4MDemuxer::with_input_len) to get them.
 pub const Y4M_DESC: &dyn Descriptor = &Des {
     d: Descr {
         name: "y4m-rs",
@@ -141,12 +1385,27 @@ pub const Y4M_DESC: &dyn Descriptor = &Des {
     },
 };
 
+/// Entry points of the fuzz targets
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzzing {
+    pub fn header(data: &[u8]) {
+        let _ = super::header(data, super::ParseMode::Strict, &mut Vec::new());
+        let _ = super::header(data, super::ParseMode::Lenient, &mut Vec::new());
+    }
+
+    pub fn frame_header(data: &[u8]) {
+        let _ = super::frame_header(data, super::ParseMode::Strict);
+        let _ = super::frame_header(data, super::ParseMode::Lenient);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use av_format::buffer::AccReader;
     use av_format::demuxer::Context;
-    use std::io::Cursor;
+    use std::io::{BufRead, Cursor};
 
     const Y4M: &[u8] = include_bytes!("../assets/test.y4m");
 
@@ -169,6 +1428,248 @@ mod tests {
         }
 
         trace!("global info: {:#?}", demuxer.info);
+
+        assert_eq!(demuxer.info.streams[0].timebase, Rational64::new(1, 25));
+
+        match demuxer.info.streams[0].params.kind {
+            Some(MediaKind::Video(ref info)) => {
+                assert_eq!(info.width, 384);
+                assert_eq!(info.height, 288);
+                assert_eq!(
+                    info.format.as_deref(),
+                    Some(&Colorspace::C420jpeg.formaton())
+                );
+            }
+            _ => panic!("expected a video stream"),
+        }
+    }
+
+    #[test]
+    fn frame_count() {
+        let demuxer = Y4MDemuxer::new().with_input_len(Y4M.len() as u64);
+        let input = Box::new(AccReader::new(Cursor::new(Y4M)));
+        let mut demuxer = Context::new(Box::new(demuxer), input);
+        demuxer.read_headers().unwrap();
+
+        assert_eq!(demuxer.info.duration, Some(51));
+        assert_eq!(demuxer.info.timebase, Some(Rational64::new(1, 25)));
+        assert_eq!(demuxer.info.streams[0].duration, Some(51));
+
+        let demuxer = Y4MDemuxer::new().with_input_len(Y4M.len() as u64 - 1);
+        let input = Box::new(AccReader::new(Cursor::new(Y4M)));
+        let mut demuxer = Context::new(Box::new(demuxer), input);
+        demuxer.read_headers().unwrap();
+
+        assert_eq!(demuxer.info.duration, None);
+    }
+
+    fn open(data: &'static [u8]) -> (Y4MDemuxer, Box<dyn Buffered>, GlobalInfo) {
+        let mut demuxer = Y4MDemuxer::new().with_input_len(data.len() as u64);
+        let mut buf: Box<dyn Buffered> = Box::new(AccReader::new(Cursor::new(data)));
+        let mut info = GlobalInfo {
+            duration: None,
+            timebase: None,
+            streams: Vec::new(),
+        };
+        buf.fill_buf().unwrap();
+        let seek = demuxer.read_headers(&buf, &mut info).unwrap();
+        buf.seek(seek).unwrap();
+        (demuxer, buf, info)
+    }
+
+    fn next_packet(demuxer: &mut Y4MDemuxer, buf: &mut Box<dyn Buffered>) -> Packet {
+        loop {
+            buf.fill_buf().unwrap();
+            let (seek, event) = demuxer.read_event(buf).unwrap();
+            buf.seek(seek).unwrap();
+            match event {
+                Event::NewPacket(pkt) => return pkt,
+                Event::MoreDataNeeded(sz) => buf.grow(sz),
+                _ => panic!("unexpected event"),
+            }
+        }
+    }
+
+    /// Hands out the data a few bytes at a time, like a pipe would
+    struct Trickle(Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(7);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    impl Seek for Trickle {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    #[test]
+    fn incremental_parsing() {
+        let mut data = b"YUV4MPEG2 W2 H2 F25:1 Ip A1:1 XCOLORRANGE=FULL\n".to_vec();
+        for i in 0..3u8 {
+            data.extend_from_slice(b"FRAME Xframe=a\n");
+            data.extend_from_slice(&[i; 6]);
+        }
+
+        let mut demuxer = Y4MDemuxer::new();
+        let mut buf: Box<dyn Buffered> = Box::new(AccReader::new(Trickle(Cursor::new(data))));
+        let mut info = GlobalInfo {
+            duration: None,
+            timebase: None,
+            streams: Vec::new(),
+        };
+
+        let mut retries = 0;
+        loop {
+            buf.fill_buf().unwrap();
+            match demuxer.read_headers(&buf, &mut info) {
+                Ok(seek) => {
+                    buf.seek(seek).unwrap();
+                    break;
+                }
+                Err(Error::MoreDataNeeded(sz)) => {
+                    retries += 1;
+                    buf.grow(sz);
+                }
+                Err(e) => panic!("error: {:?}", e),
+            }
+        }
+        assert!(retries > 0);
+        assert_eq!(info.streams.len(), 1);
+
+        for i in 0..3u8 {
+            let pkt = next_packet(&mut demuxer, &mut buf);
+            assert_eq!(pkt.data, [i; 6]);
+        }
+    }
+
+    #[test]
+    fn truncated_header_at_eof() {
+        let input = Box::new(AccReader::new(Cursor::new(&b"YUV4MPEG2 W2 H2"[..])));
+        let mut demuxer = Context::new(Y4M_DESC.create(), input);
+        match demuxer.read_headers() {
+            Err(Error::Io(e)) => assert_eq!(
+                e.get_ref().unwrap().downcast_ref::<Y4MError>(),
+                Some(&Y4MError::TruncatedHeader { offset: 15 })
+            ),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn seek_fixed_size() {
+        let (mut demuxer, mut buf, _) = open(Y4M);
+
+        let seek = demuxer.seek_frame(&mut buf, 42).unwrap();
+        assert_eq!(seek, SeekFrom::Start(34 + 42 * 165_894));
+        buf.seek(seek).unwrap();
+        let pkt = next_packet(&mut demuxer, &mut buf);
+        assert_eq!(pkt.t.pts, Some(42));
+        assert_eq!(pkt.pos, Some(34 + 42 * 165_894));
+
+        let seek = demuxer.seek_timestamp(&mut buf, 3).unwrap();
+        buf.seek(seek).unwrap();
+        let pkt = next_packet(&mut demuxer, &mut buf);
+        assert_eq!(pkt.t.pts, Some(3));
+        assert_eq!(&pkt.data[..], &Y4M[34 + 3 * 165_894 + 6..34 + 4 * 165_894]);
+
+        assert!(demuxer.seek_frame(&mut buf, 52).is_err());
+    }
+
+    #[test]
+    fn seek_variable_size() {
+        let mut data = b"YUV4MPEG2 W2 H2 F25:1\n".to_vec();
+        for i in 0..5u8 {
+            if i % 2 == 0 {
+                data.extend_from_slice(b"FRAME Ib\n");
+            } else {
+                data.extend_from_slice(b"FRAME\n");
+            }
+            data.extend_from_slice(&[i; 6]);
+        }
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+        let (mut demuxer, mut buf, _) = open(data);
+
+        for frame in [3, 1, 4, 0] {
+            let seek = demuxer.seek_frame(&mut buf, frame).unwrap();
+            buf.seek(seek).unwrap();
+            let pkt = next_packet(&mut demuxer, &mut buf);
+            assert_eq!(pkt.t.pts, Some(frame as i64));
+            assert_eq!(pkt.data, [frame as u8; 6]);
+            let params = FrameParams::from_packet(&pkt);
+            if frame % 2 == 0 {
+                assert_eq!(
+                    params.and_then(|p| p.interlace()),
+                    Some(Interlace::BottomFieldFirst)
+                );
+            } else {
+                assert!(params.is_none());
+            }
+        }
+
+        assert_eq!(
+            demuxer.seek_frame(&mut buf, 5).unwrap(),
+            SeekFrom::Start(data.len() as u64)
+        );
+        assert!(demuxer.seek_frame(&mut buf, 6).is_err());
+    }
+
+    #[test]
+    fn header_roundtrip() {
+        let line = "YUV4MPEG2 W320 H240 F30000:1001 It A1:1 C422p10 XYSCSS=422P10";
+        let (_, hdr) = header(
+            format!("{}\n", line).as_bytes(),
+            ParseMode::Lenient,
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(hdr.to_string(), line);
+    }
+
+    #[test]
+    fn colorspace_formaton() {
+        let fmt = Colorspace::Cmono.formaton();
+        assert_eq!(fmt.components, 1);
+
+        let fmt = Colorspace::C444alpha.formaton();
+        assert_eq!(fmt.components, 4);
+        assert!(fmt.alpha);
+
+        let fmt = Colorspace::C420mpeg2.formaton();
+        assert_eq!(fmt.chroma_location, ChromaLocation::Left);
+
+        let fmt = Colorspace::C422p12.formaton();
+        let chroma = fmt.comp_info[1].unwrap();
+        assert_eq!((chroma.h_ss, chroma.v_ss, chroma.depth), (1, 0, 12));
+
+        let fmt = Colorspace::Cmono10.formaton();
+        assert_eq!(fmt.components, 1);
+        assert_eq!(fmt.comp_info[0].unwrap().depth, 10);
+        assert_eq!(Colorspace::Cmono10.bytes_per_sample(), 2);
+
+        for cs in [
+            Colorspace::C420jpeg,
+            Colorspace::C420mpeg2,
+            Colorspace::C420paldv,
+            Colorspace::C411,
+            Colorspace::C422,
+            Colorspace::C444,
+            Colorspace::C444alpha,
+            Colorspace::Cmono,
+            Colorspace::Cmono9,
+            Colorspace::Cmono16,
+            Colorspace::C420p9,
+            Colorspace::C420p10,
+            Colorspace::C422p12,
+            Colorspace::C422p14,
+            Colorspace::C444p16,
+        ] {
+            assert_eq!(Colorspace::from_tag(cs.tag()), Some(cs));
+            assert_eq!(Colorspace::from_formaton(&cs.formaton()), Some(cs));
+        }
     }
 
     #[test]
@@ -183,6 +1684,7 @@ mod tests {
 
         trace!("global info: {:#?}", demuxer.info);
 
+        let mut packets = 0;
         loop {
             match demuxer.read_event() {
                 Ok(event) => match event {
@@ -190,6 +1692,10 @@ mod tests {
                     Event::NewStream(s) => panic!("new stream :{:?}", s),
                     Event::NewPacket(packet) => {
                         debug!("received packet with pos: {:?}", packet.pos);
+                        assert_eq!(packet.data.len(), 384 * 288 * 3 / 2);
+                        assert_eq!(packet.t.pts, Some(packets));
+                        assert_eq!(packet.t.duration, Some(1));
+                        packets += 1;
                     }
                     Event::Continue => continue,
                     Event::Eof => {
@@ -203,5 +1709,322 @@ mod tests {
                 }
             }
         }
+        assert_eq!(packets, 51);
+    }
+
+    #[test]
+    fn parse_all_tags() {
+        let (rest, hdr) = header(
+            b"YUV4MPEG2 C444p10 XYSCSS=444P10 A1:1 It F30000:1001 H240 W320 Xfoo\nFRAME",
+            ParseMode::Strict,
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(rest, b"FRAME");
+        assert_eq!(hdr.width(), 320);
+        assert_eq!(hdr.height(), 240);
+        assert_eq!(hdr.framerate(), Ratio::new(30000, 1001));
+        assert_eq!(hdr.timebase(), Rational64::new(1001, 30000));
+        assert_eq!(hdr.interlace(), Interlace::TopFieldFirst);
+        assert_eq!(hdr.aspect(), Ratio::new(1, 1));
+        assert_eq!(hdr.colorspace(), Colorspace::C444p10);
+        assert_eq!(hdr.extensions(), ["YSCSS=444P10", "foo"]);
+        assert_eq!(hdr.frame_size(), 320 * 240 * 3 * 2);
+
+        let (_, hdr) = header(&Y4M[..64], ParseMode::Lenient, &mut Vec::new()).unwrap();
+        assert_eq!(hdr.framerate(), Ratio::new(25, 1));
+        assert_eq!(hdr.interlace(), Interlace::Progressive);
+        assert!(!hdr.aspect().is_known());
+        assert_eq!(hdr.timebase(), Rational64::new(1, 25));
+        assert_eq!(hdr.colorspace(), Colorspace::C420jpeg);
+    }
+
+    #[test]
+    fn header_errors() {
+        let err =
+            |data: &[u8]| parse_header(data, ParseMode::Lenient, &mut Vec::new()).unwrap_err();
+
+        assert_eq!(
+            err(b"YUV4MPEG W320 H240\n"),
+            Y4MError::MissingMagic { offset: 0 }
+        );
+        assert_eq!(
+            err(b"YUV4MPEG2 H240\n"),
+            Y4MError::MissingWidth { offset: 15 }
+        );
+        assert_eq!(
+            err(b"YUV4MPEG2 W320\n"),
+            Y4MError::MissingHeight { offset: 15 }
+        );
+        assert_eq!(
+            err(b"YUV4MPEG2 Wfoo H240\n"),
+            Y4MError::InvalidDimension { offset: 10 }
+        );
+        assert_eq!(
+            err(b"YUV4MPEG2 W320 H240 F25\n"),
+            Y4MError::MalformedRatio { offset: 20 }
+        );
+        assert_eq!(
+            err(b"YUV4MPEG2 W320 H240 F9223372036854775808:1\n"),
+            Y4MError::MalformedRatio { offset: 20 }
+        );
+        assert_eq!(
+            err(b"YUV4MPEG2 W320 H240 Cfoo\n"),
+            Y4MError::UnknownColorspace {
+                offset: 20,
+                value: "foo".to_owned()
+            }
+        );
+        assert_eq!(
+            err(b"YUV4MPEG2 W320 H24"),
+            Y4MError::TruncatedHeader { offset: 18 }
+        );
+        assert_eq!(err(b"YUV4"), Y4MError::TruncatedHeader { offset: 4 });
+        assert!(matches!(
+            header(b"YUV4", ParseMode::Lenient, &mut Vec::new()),
+            Err(nom::Err::Incomplete(_))
+        ));
+        assert!(matches!(
+            header(b"YUV4MPEG2 W320 H2", ParseMode::Lenient, &mut Vec::new()),
+            Err(nom::Err::Incomplete(_))
+        ));
+        assert_eq!(
+            err(b"YUV4MPEG2 W320 H240 X\xff\n"),
+            Y4MError::InvalidUtf8 { offset: 21 }
+        );
+        assert_eq!(
+            err(format!("YUV4MPEG2 W{} H{}\n", usize::MAX, usize::MAX).as_bytes()),
+            Y4MError::FrameSizeOverflow {
+                width: usize::MAX,
+                height: usize::MAX
+            }
+        );
+    }
+
+    fn demux_with_limits(data: Vec<u8>, limits: Limits) -> Result<usize> {
+        let demuxer = Y4MDemuxer::new().with_limits(limits);
+        let input = Box::new(AccReader::new(Cursor::new(data)));
+        let mut demuxer = Context::new(Box::new(demuxer), input);
+        demuxer.read_headers()?;
+
+        let mut packets = 0;
+        loop {
+            match demuxer.read_event()? {
+                Event::NewPacket(_) => packets += 1,
+                Event::Eof => return Ok(packets),
+                _ => {}
+            }
+        }
+    }
+
+    fn limit_exceeded(res: Result<usize>) -> Y4MError {
+        match res {
+            Err(Error::Io(e)) => e
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<Y4MError>())
+                .cloned()
+                .unwrap(),
+            _ => panic!("expected a typed error"),
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits {
+            max_width: 384,
+            max_height: 288,
+            max_pixels: 384 * 288,
+            ..Default::default()
+        };
+        assert_eq!(demux_with_limits(Y4M.to_vec(), limits).unwrap(), 51);
+
+        let err = limit_exceeded(demux_with_limits(
+            b"YUV4MPEG2 W999999999 H999999999 C444p16\n".to_vec(),
+            Default::default(),
+        ));
+        assert_eq!(
+            err,
+            Y4MError::LimitExceeded {
+                what: "width",
+                limit: 32768
+            }
+        );
+
+        let limits = Limits {
+            max_pixels: 100,
+            ..Default::default()
+        };
+        let err = limit_exceeded(demux_with_limits(b"YUV4MPEG2 W20 H20\n".to_vec(), limits));
+        assert_eq!(
+            err,
+            Y4MError::LimitExceeded {
+                what: "pixel count",
+                limit: 100
+            }
+        );
+
+        let limits = Limits {
+            max_header_len: 16,
+            ..Default::default()
+        };
+        let err = limit_exceeded(demux_with_limits(Y4M.to_vec(), limits));
+        assert_eq!(
+            err,
+            Y4MError::LimitExceeded {
+                what: "header length",
+                limit: 16
+            }
+        );
+
+        let limits = Limits {
+            max_extensions: 1,
+            ..Default::default()
+        };
+        let err = limit_exceeded(demux_with_limits(
+            b"YUV4MPEG2 W2 H2 Xa Xb\n".to_vec(),
+            limits,
+        ));
+        assert_eq!(
+            err,
+            Y4MError::LimitExceeded {
+                what: "X tag count",
+                limit: 1
+            }
+        );
+
+        let limits = Limits {
+            max_frame_params_len: 4,
+            ..Default::default()
+        };
+        let mut data = b"YUV4MPEG2 W2 H2\nFRAME Ib\n".to_vec();
+        data.extend_from_slice(&[0; 6]);
+        assert_eq!(demux_with_limits(data.clone(), limits).unwrap(), 1);
+        data.extend_from_slice(b"FRAME Ib Xabc\n");
+        data.extend_from_slice(&[0; 6]);
+        let err = limit_exceeded(demux_with_limits(data, limits));
+        assert_eq!(
+            err,
+            Y4MError::LimitExceeded {
+                what: "frame parameters length",
+                limit: 4
+            }
+        );
+    }
+
+    #[test]
+    fn parse_modes() {
+        let quirky = b"YUV4MPEG2 w320 H240  W352 F25:1 \r\nFRAME Ib \r\n";
+        let err = parse_header(quirky, ParseMode::Strict, &mut Vec::new()).unwrap_err();
+        assert_eq!(err, Y4MError::LowercaseTag { offset: 10 });
+
+        let mut warnings = Vec::new();
+        let (rest, hdr) = parse_header(quirky, ParseMode::Lenient, &mut warnings).unwrap();
+        assert_eq!((hdr.width(), hdr.height()), (352, 240));
+        assert_eq!(
+            warnings,
+            [
+                Y4MError::LowercaseTag { offset: 10 },
+                Y4MError::UnexpectedSpace { offset: 19 },
+                Y4MError::DuplicateTag {
+                    offset: 21,
+                    tag: 'W'
+                },
+                Y4MError::UnexpectedSpace { offset: 31 },
+                Y4MError::CarriageReturn { offset: 32 },
+                Y4MError::MissingColorspace { offset: 34 },
+            ]
+        );
+
+        assert!(frame_header(rest, ParseMode::Strict).is_err());
+        let (_, params) = frame_header(rest, ParseMode::Lenient).unwrap();
+        assert_eq!(params.interlace(), Some(Interlace::BottomFieldFirst));
+
+        fn strict(data: &[u8]) -> std::result::Result<(&[u8], Y4MHeader), Y4MError> {
+            parse_header(data, ParseMode::Strict, &mut Vec::new())
+        }
+        assert!(strict(b"YUV4MPEG2 W2 H2 C420jpeg\n").is_ok());
+        let (_, hdr) = strict(b"YUV4MPEG2 W2 H2\n").unwrap();
+        assert_eq!(hdr.colorspace(), Colorspace::C420jpeg);
+        assert_eq!(
+            strict(b"YUV4MPEG2 W2 H2 C420jpeg H2\n").unwrap_err(),
+            Y4MError::DuplicateTag {
+                offset: 25,
+                tag: 'H'
+            }
+        );
+    }
+
+    #[test]
+    fn header_warnings() {
+        let demuxer = Y4MDemuxer::new().with_mode(ParseMode::Strict);
+        let input = Box::new(AccReader::new(Cursor::new(Y4M)));
+        let mut demuxer = Context::new(Box::new(demuxer), input);
+        demuxer.read_headers().unwrap();
+
+        let mut demuxer = Y4MDemuxer::new();
+        let mut input = AccReader::new(Cursor::new(Y4M));
+        input.fill_buf().unwrap();
+        let input: Box<dyn Buffered> = Box::new(input);
+        let mut info = GlobalInfo {
+            duration: None,
+            timebase: None,
+            streams: Vec::new(),
+        };
+        demuxer.read_headers(&input, &mut info).unwrap();
+        assert_eq!(
+            demuxer.warnings(),
+            [Y4MError::MissingColorspace { offset: 34 }]
+        );
+    }
+
+    #[test]
+    fn probe() {
+        assert_eq!(Y4M_DESC.probe(Y4M), PROBE_SCORE_HEADER);
+        assert_eq!(Y4M_DESC.probe(&Y4M[..34]), PROBE_SCORE_HEADER);
+        assert_eq!(Y4M_DESC.probe(&Y4M[..20]), PROBE_SCORE_MAGIC);
+        assert_eq!(Y4M_DESC.probe(&Y4M[..9]), PROBE_SCORE_MAGIC);
+        assert_eq!(Y4M_DESC.probe(&Y4M[..5]), 0);
+        assert_eq!(Y4M_DESC.probe(b""), 0);
+        assert_eq!(Y4M_DESC.probe(b"RIFF\0\0\0\0WAVEfmt "), 0);
+        assert_eq!(Y4M_DESC.probe(b"YUV4MPEG2 W0 H2\n"), 0);
+        assert_eq!(Y4M_DESC.probe(b"YUV4MPEG2  W2\n"), 0);
+    }
+
+    #[test]
+    fn typed_error_conversion() {
+        let input = Box::new(AccReader::new(Cursor::new(&b"YUV4MPEG2 W320 Cfoo\n"[..])));
+        let mut demuxer = Context::new(Y4M_DESC.create(), input);
+
+        match demuxer.read_headers() {
+            Err(Error::Io(e)) => {
+                let e = e.get_ref().unwrap().downcast_ref::<Y4MError>().unwrap();
+                assert_eq!(
+                    *e,
+                    Y4MError::UnknownColorspace {
+                        offset: 15,
+                        value: "foo".to_owned()
+                    }
+                );
+            }
+            _ => panic!("expected a typed error"),
+        }
+    }
+
+    #[test]
+    fn frame_header_params() {
+        let (rest, params) = frame_header(b"FRAME Ib Xfoo=bar\n\x10", ParseMode::Strict).unwrap();
+        assert_eq!(params.interlace(), Some(Interlace::BottomFieldFirst));
+        assert_eq!(params.extensions(), ["foo=bar"]);
+        assert_eq!(params.to_string(), " Ib Xfoo=bar");
+        assert_eq!(rest, b"\x10");
+
+        let (_, params) = frame_header(b"FRAME\n", ParseMode::Strict).unwrap();
+        assert!(params.is_empty());
+        assert!(frame_header(b"FRAME Iz\n", ParseMode::Strict).is_err());
+
+        assert!(matches!(
+            frame_header(b"FRA", ParseMode::Strict),
+            Err(nom::Err::Incomplete(_))
+        ));
     }
 }
@@ -0,0 +1,207 @@
+use crate::demuxer::{Colorspace, Y4MHeader};
+use av_codec::encoder::Encoder;
+use av_codec::error::*;
+use av_data::frame::{ArcFrame, MediaKind};
+use av_data::packet::Packet;
+use av_data::params::{self, CodecParams};
+use av_data::pixel::Formaton;
+use av_data::value::Value;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Packs frames into Y4M packets
+///
+/// Frames may use any stride, their planes are stored tightly in the packets.
+#[derive(Default)]
+pub struct Y4MEncoder {
+    params: Option<(Y4MHeader, Arc<Formaton>)>,
+    configured: bool,
+    packets: VecDeque<Packet>,
+}
+
+impl Y4MEncoder {
+    pub fn new() -> Y4MEncoder {
+        Default::default()
+    }
+
+    fn encode(&self, frame: &ArcFrame) -> Result<Packet> {
+        let (header, _) = self.params.as_ref().ok_or(Error::ConfigurationIncomplete)?;
+        let info = match frame.kind {
+            MediaKind::Video(ref info) => info,
+            _ => return Err(Error::InvalidData),
+        };
+        if info.width != header.width() || info.height != header.height() {
+            error!(
+                "frame of {}x{}, expected {}x{}",
+                info.width,
+                info.height,
+                header.width(),
+                header.height()
+            );
+            return Err(Error::InvalidData);
+        }
+        let colorspace = header.colorspace();
+        if Colorspace::from_formaton(&info.format) != Some(colorspace) {
+            error!(
+                "frame format {:?} does not match {:?}",
+                info.format, colorspace
+            );
+            return Err(Error::InvalidData);
+        }
+
+        let mut pkt = Packet::with_capacity(header.frame_size());
+        for plane in 0..colorspace.planes() {
+            let (width, height) = header.plane_dimensions(plane);
+            let row = width * colorspace.bytes_per_sample();
+            let src = frame
+                .buf
+                .as_slice_inner(plane)
+                .map_err(|_| Error::InvalidData)?;
+            let linesize = frame.buf.linesize(plane).map_err(|_| Error::InvalidData)?;
+            let len = linesize
+                .checked_mul(height.saturating_sub(1))
+                .and_then(|len| len.checked_add(row))
+                .ok_or(Error::InvalidData)?;
+            if height > 0 && (linesize < row || src.len() < len) {
+                return Err(Error::InvalidData);
+            }
+
+            for y in 0..height {
+                pkt.data
+                    .extend_from_slice(&src[y * linesize..y * linesize + row]);
+            }
+        }
+
+        pkt.t = frame.t.clone();
+        pkt.is_key = true;
+
+        Ok(pkt)
+    }
+}
+
+impl Encoder for Y4MEncoder {
+    fn get_extradata(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn send_frame(&mut self, frame: &ArcFrame) -> Result<()> {
+        if !self.configured {
+            return Err(Error::ConfigurationIncomplete);
+        }
+        let pkt = self.encode(frame)?;
+        self.packets.push_back(pkt);
+        Ok(())
+    }
+
+    fn receive_packet(&mut self) -> Result<Packet> {
+        self.packets.pop_front().ok_or(Error::MoreDataNeeded)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.params.is_none() {
+            return Err(Error::ConfigurationIncomplete);
+        }
+        self.configured = true;
+        Ok(())
+    }
+
+    fn set_option<'a>(&mut self, _key: &str, _val: Value<'a>) -> Result<()> {
+        Err(Error::ConfigurationInvalid)
+    }
+
+    fn get_params(&self) -> Result<CodecParams> {
+        let (header, format) = self.params.as_ref().ok_or(Error::ConfigurationIncomplete)?;
+        Ok(CodecParams {
+            extradata: None,
+            codec_id: None,
+            bit_rate: 0,
+            delay: 0,
+            convergence_window: 0,
+            kind: Some(params::MediaKind::Video(params::VideoInfo {
+                width: header.width(),
+                height: header.height(),
+                format: Some(format.clone()),
+            })),
+        })
+    }
+
+    fn set_params(&mut self, params: &CodecParams) -> Result<()> {
+        let (header, format) =
+            Y4MHeader::from_codec_params(params).ok_or(Error::ConfigurationInvalid)?;
+        self.params = Some((header, format));
+        self.configured = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::tests::params;
+    use crate::decoder::Y4MDecoder;
+    use av_codec::decoder::Decoder;
+
+    fn roundtrip(width: usize, height: usize, cs: Colorspace) {
+        let params = params(width, height, cs);
+        let mut dec = Y4MDecoder::new(&params).unwrap();
+        let mut enc = Y4MEncoder::new();
+        enc.set_params(&params).unwrap();
+        enc.configure().unwrap();
+
+        let size = Y4MHeader {
+            width,
+            height,
+            colorspace: cs,
+            ..Default::default()
+        }
+        .frame_size();
+        let mut pkt = Packet::with_capacity(size);
+        pkt.data.extend((0..size).map(|i| i as u8));
+        pkt.t.pts = Some(7);
+        dec.send_packet(&pkt).unwrap();
+
+        // Decoded frames have padded strides
+        let frame = dec.receive_frame().unwrap();
+        enc.send_frame(&frame).unwrap();
+        let out = enc.receive_packet().unwrap();
+        assert_eq!(out.data, pkt.data);
+        assert_eq!(out.t.pts, Some(7));
+    }
+
+    #[test]
+    fn encode_roundtrip() {
+        roundtrip(7, 5, Colorspace::C420mpeg2);
+        roundtrip(9, 3, Colorspace::C411);
+        roundtrip(4, 4, Colorspace::C444alpha);
+        roundtrip(5, 2, Colorspace::C422p12);
+        roundtrip(3, 3, Colorspace::Cmono);
+    }
+
+    #[test]
+    fn reject_mismatched_frame() {
+        let mut dec = Y4MDecoder::new(&params(2, 2, Colorspace::C420jpeg)).unwrap();
+        let mut pkt = Packet::with_capacity(6);
+        pkt.data.extend_from_slice(&[0; 6]);
+        dec.send_packet(&pkt).unwrap();
+        let frame = dec.receive_frame().unwrap();
+
+        let mut enc = Y4MEncoder::new();
+        assert!(enc.send_frame(&frame).is_err());
+
+        enc.set_params(&params(4, 2, Colorspace::C420jpeg)).unwrap();
+        enc.configure().unwrap();
+        assert!(enc.send_frame(&frame).is_err());
+
+        enc.set_params(&params(2, 2, Colorspace::C444)).unwrap();
+        enc.configure().unwrap();
+        assert!(enc.send_frame(&frame).is_err());
+
+        assert!(enc
+            .set_params(&params(usize::MAX, 2, Colorspace::C444))
+            .is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use std::io;
+use thiserror::Error;
+
+/// Problems found while parsing a YUV4MPEG2 stream
+///
+/// Offsets are in bytes from the start of the stream.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum Y4MError {
+    #[error("missing YUV4MPEG2 magic at offset {offset}")]
+    MissingMagic { offset: usize },
+    #[error("missing W tag in the header ending at offset {offset}")]
+    MissingWidth { offset: usize },
+    #[error("missing H tag in the header ending at offset {offset}")]
+    MissingHeight { offset: usize },
+    #[error("invalid dimension at offset {offset}")]
+    InvalidDimension { offset: usize },
+    #[error("malformed ratio at offset {offset}")]
+    MalformedRatio { offset: usize },
+    #[error("invalid interlacing at offset {offset}")]
+    InvalidInterlace { offset: usize },
+    #[error("unknown colorspace {value:?} at offset {offset}")]
+    UnknownColorspace { offset: usize, value: String },
+    #[error("malformed tag at offset {offset}")]
+    MalformedTag { offset: usize },
+    #[error("header truncated at offset {offset}")]
+    TruncatedHeader { offset: usize },
+    #[error("invalid UTF-8 at offset {offset}")]
+    InvalidUtf8 { offset: usize },
+    #[error("frame size of a {width}x{height} picture overflows")]
+    FrameSizeOverflow { width: usize, height: usize },
+    #[error("invalid frame header at offset {offset}")]
+    InvalidFrameHeader { offset: usize },
+    #[error("missing C tag in the header ending at offset {offset}")]
+    MissingColorspace { offset: usize },
+    #[error("carriage return before the newline at offset {offset}")]
+    CarriageReturn { offset: usize },
+    #[error("unexpected space at offset {offset}")]
+    UnexpectedSpace { offset: usize },
+    #[error("lowercase tag at offset {offset}")]
+    LowercaseTag { offset: usize },
+    #[error("duplicate {tag} tag at offset {offset}")]
+    DuplicateTag { offset: usize, tag: char },
+    #[error("{what} exceeds the limit of {limit}")]
+    LimitExceeded { what: &'static str, limit: usize },
+}
+
+/// The typed error is kept as the source of an `InvalidData` I/O error
+impl From<Y4MError> for io::Error {
+    fn from(e: Y4MError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+impl From<Y4MError> for av_format::error::Error {
+    fn from(e: Y4MError) -> Self {
+        av_format::error::Error::Io(e.into())
+    }
+}
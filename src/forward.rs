@@ -0,0 +1,137 @@
+//! Demuxing from pipes and other forward-only inputs
+
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+
+/// Makes a forward-only input, such as stdin, usable where `Seek` is needed
+///
+/// Seeking forward skips data, seeking backward or from the end fails with an
+/// `Unsupported` error. Demuxing works as usual, and the frame count stays
+/// unknown unless the input length is given. Since seeking forward discards
+/// data, build the demuxer with [`Y4MDemuxer::with_seekable`] so that
+/// [`Y4MDemuxer::seek_frame`] fails with that error before touching the input.
+///
+/// [`Y4MDemuxer::with_seekable`]: crate::demuxer::Y4MDemuxer::with_seekable
+/// [`Y4MDemuxer::seek_frame`]: crate::demuxer::Y4MDemuxer::seek_frame
+pub struct ForwardOnly<R: Read> {
+    input: R,
+    pos: u64,
+}
+
+impl<R: Read> ForwardOnly<R> {
+    pub fn new(input: R) -> ForwardOnly<R> {
+        ForwardOnly { input, pos: 0 }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+pub(crate) fn unsupported() -> io::Error {
+    io::Error::new(ErrorKind::Unsupported, "unsupported on non-seekable input")
+}
+
+impl<R: Read> Read for ForwardOnly<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.input.read(buf)?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<R: Read> Seek for ForwardOnly<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(pos) => pos,
+            SeekFrom::Current(offset) => self
+                .pos
+                .checked_add_signed(offset)
+                .ok_or_else(unsupported)?,
+            SeekFrom::End(_) => return Err(unsupported()),
+        };
+        if target < self.pos {
+            return Err(unsupported());
+        }
+
+        // Seeking past the end stops there
+        let skip = target - self.pos;
+        io::copy(&mut self.by_ref().take(skip), &mut io::sink())?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demuxer::Y4MDemuxer;
+    use av_format::buffer::{AccReader, Buffered};
+    use av_format::common::GlobalInfo;
+    use av_format::demuxer::{Context, Demuxer, Event};
+    use av_format::error::Error;
+    use std::io::BufRead;
+
+    const Y4M: &[u8] = include_bytes!("../assets/test.y4m");
+
+    #[test]
+    fn skip_forward() {
+        let mut input = ForwardOnly::new(&b"0123456789"[..]);
+        assert_eq!(input.seek(SeekFrom::Current(2)).unwrap(), 2);
+        assert_eq!(input.seek(SeekFrom::Start(5)).unwrap(), 5);
+        assert_eq!(input.stream_position().unwrap(), 5);
+        let mut byte = [0];
+        input.read_exact(&mut byte).unwrap();
+        assert_eq!(&byte, b"5");
+
+        let err = input.seek(SeekFrom::Start(0)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert!(input.seek(SeekFrom::End(0)).is_err());
+        assert_eq!(input.seek(SeekFrom::Current(100)).unwrap(), 10);
+    }
+
+    #[test]
+    fn demux_forward_only() {
+        let input = Box::new(AccReader::new(ForwardOnly::new(Y4M)));
+        let mut demuxer = Context::new(Box::new(Y4MDemuxer::new()), input);
+        demuxer.read_headers().unwrap();
+        assert_eq!(demuxer.info.duration, None);
+
+        let mut packets = 0;
+        loop {
+            match demuxer.read_event().unwrap() {
+                Event::NewPacket(pkt) => {
+                    assert_eq!(pkt.t.pts, Some(packets));
+                    packets += 1;
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(packets, 51);
+    }
+
+    #[test]
+    fn seek_forward_only() {
+        let mut demuxer = Y4MDemuxer::new().with_seekable(false);
+        let mut input = AccReader::new(ForwardOnly::new(Y4M));
+        input.fill_buf().unwrap();
+        let input: Box<dyn Buffered> = Box::new(input);
+        let mut info = GlobalInfo {
+            duration: None,
+            timebase: None,
+            streams: Vec::new(),
+        };
+        demuxer.read_headers(&input, &mut info).unwrap();
+        assert_eq!(demuxer.frame_count(), None);
+
+        // Past the first frame marker
+        let mut input = ForwardOnly::new(Y4M);
+        input.seek(SeekFrom::Start(100)).unwrap();
+        for frame in [0, 10] {
+            match demuxer.seek_frame(&mut input, frame) {
+                Err(Error::Io(e)) => assert_eq!(e.kind(), ErrorKind::Unsupported),
+                res => panic!("unexpected result {:?}", res),
+            }
+            assert_eq!(input.stream_position().unwrap(), 100);
+        }
+    }
+}
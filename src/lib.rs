@@ -1,11 +1,32 @@
+extern crate av_codec;
 extern crate av_data;
 extern crate av_format;
-#[macro_use]
 extern crate nom;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+extern crate thiserror;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 
 #[cfg(test)]
 extern crate pretty_env_logger;
 
+#[cfg(feature = "tokio")]
+pub mod aio;
+pub mod decoder;
 pub mod demuxer;
+pub mod encoder;
+pub mod error;
+pub mod forward;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod muxer;
+pub mod pool;
+pub mod prefetch;
+pub mod reader;
+pub mod writer;
+
+pub use reader::Reader;
+pub use writer::{Writer, WriterBuilder};
@@ -0,0 +1,187 @@
+//! Zero-copy reading of memory-mapped streams
+
+use crate::demuxer::{FrameParams, Limits, ParseMode, Y4MHeader};
+use crate::error::Y4MError;
+use crate::reader::{frame_line_len, frame_params, header_line_len, stream_header, Frame};
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+/// Reads a YUV4MPEG2 stream held in memory, usually a memory-mapped file
+///
+/// Frames are indexed once on creation, then handed out as slices of the
+/// stream without copying their data.
+pub struct MappedReader<D: AsRef<[u8]> = Mmap> {
+    data: D,
+    header: Y4MHeader,
+    warnings: Vec<Y4MError>,
+    /// Offset of the plane data of each frame, along with its parameters
+    index: Vec<(usize, FrameParams)>,
+}
+
+impl MappedReader<Mmap> {
+    /// Maps the file at `path` and indexes its frames
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this process or any
+    /// other, while the reader or any frame borrowed from it is alive. See
+    /// [`Mmap::map`].
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<MappedReader<Mmap>> {
+        let file = File::open(path)?;
+        // SAFETY: upheld by the caller
+        let map = unsafe { Mmap::map(&file)? };
+        MappedReader::new(map)
+    }
+}
+
+impl<D: AsRef<[u8]>> MappedReader<D> {
+    /// Indexes the frames of `data` with the default mode and limits
+    pub fn new(data: D) -> io::Result<MappedReader<D>> {
+        MappedReader::with_options(data, ParseMode::default(), Limits::default())
+    }
+
+    /// Indexes the frames of `data`, parsing and bounding the stream as
+    /// requested
+    pub fn with_options(data: D, mode: ParseMode, limits: Limits) -> io::Result<MappedReader<D>> {
+        let bytes = data.as_ref();
+        let mut warnings = Vec::new();
+        let line = next_line(bytes, header_line_len(&limits));
+        let header = stream_header(line, mode, &limits, &mut warnings)?;
+        let frame_size = header.frame_size();
+
+        let mut index = Vec::new();
+        let mut pos = line.len();
+        while pos < bytes.len() {
+            let line = next_line(&bytes[pos..], frame_line_len(&limits));
+            let params = frame_params(line, mode, &limits, pos)?;
+            let start = pos + line.len();
+            if bytes.len() - start < frame_size {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+            index.push((start, params));
+            pos = start + frame_size;
+        }
+
+        Ok(MappedReader {
+            data,
+            header,
+            warnings,
+            index,
+        })
+    }
+
+    pub fn header(&self) -> &Y4MHeader {
+        &self.header
+    }
+
+    /// Quirks accepted while reading the stream header, in lenient mode
+    pub fn warnings(&self) -> &[Y4MError] {
+        &self.warnings
+    }
+
+    /// Number of frames in the stream
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Offset of the plane data of the given frame
+    pub fn frame_offset(&self, frame: usize) -> Option<usize> {
+        self.index.get(frame).map(|&(start, _)| start)
+    }
+
+    /// The given frame, borrowing the stream data
+    pub fn frame(&self, frame: usize) -> Option<Frame<'_>> {
+        let (start, params) = self.index.get(frame)?;
+        let data = &self.data.as_ref()[*start..*start + self.header.frame_size()];
+
+        Some(Frame {
+            width: self.header.width,
+            height: self.header.height,
+            colorspace: self.header.colorspace,
+            params: params.clone(),
+            data: Cow::Borrowed(data),
+        })
+    }
+
+    /// Iterates over the frames, borrowing the stream data
+    pub fn frames(&self) -> impl Iterator<Item = Frame<'_>> + '_ {
+        (0..self.len()).filter_map(move |frame| self.frame(frame))
+    }
+
+    pub fn into_inner(self) -> D {
+        self.data
+    }
+}
+
+/// Data up to the next newline included, at most `max` bytes
+fn next_line(data: &[u8], max: usize) -> &[u8] {
+    let data = &data[..data.len().min(max)];
+    match data.iter().position(|&c| c == b'\n') {
+        Some(end) => &data[..=end],
+        None => data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demuxer::Interlace;
+    use std::io::Write;
+
+    const Y4M: &[u8] = include_bytes!("../assets/test.y4m");
+
+    #[test]
+    fn borrowed_frames() {
+        let reader = MappedReader::new(Y4M).unwrap();
+        assert_eq!(reader.len(), 51);
+        assert_eq!(reader.header().width(), 384);
+
+        let frame = reader.frame(50).unwrap();
+        let start = 34 + 50 * 165_894 + 6;
+        assert_eq!(reader.frame_offset(50), Some(start));
+        assert_eq!(frame.data().as_ptr(), Y4M[start..].as_ptr());
+        assert_eq!(frame.data().len(), 165_888);
+        assert!(reader.frame(51).is_none());
+        assert_eq!(reader.frames().count(), 51);
+    }
+
+    #[test]
+    fn frame_params_and_errors() {
+        let data = b"YUV4MPEG2 W1 H1 Cmono\nFRAME It\n\x01FRAME\n\x02".to_vec();
+        let reader = MappedReader::new(&data[..]).unwrap();
+        let frames: Vec<_> = reader.frames().collect();
+        assert_eq!(
+            frames[0].params().interlace(),
+            Some(Interlace::TopFieldFirst)
+        );
+        assert_eq!(frames[0].data(), &[1]);
+        assert!(frames[1].params().is_empty());
+        assert_eq!(frames[1].data(), &[2]);
+
+        let err = MappedReader::new(&data[..data.len() - 1]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        let err = MappedReader::new(&data[..data.len() - 2]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn open_file() {
+        let path = std::env::temp_dir().join(format!("y4m-mmap-{}.y4m", std::process::id()));
+        File::create(&path).unwrap().write_all(Y4M).unwrap();
+
+        // SAFETY: the file is private to the test and left untouched
+        let reader = unsafe { MappedReader::open(&path) }.unwrap();
+        assert_eq!(reader.len(), 51);
+        assert_eq!(reader.frame(0).unwrap().data(), &Y4M[40..40 + 165_888]);
+        drop(reader);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,327 @@
+use crate::demuxer::{
+    check_extension, is_full_range, parse_ratio, Colorspace, FrameParams, Interlace, Ratio,
+    Y4MHeader, FULL_RANGE,
+};
+use crate::pool::payload;
+use av_data::packet::Packet;
+use av_data::params::MediaKind;
+use av_data::value::Value;
+use av_format::common::GlobalInfo;
+use av_format::error::*;
+use av_format::muxer::{Descr, Descriptor, Muxer};
+use std::io::Write;
+use std::sync::Arc;
+
+/// YUV4MPEG2 muxer
+///
+/// The stream header is built from the first video stream of the global info.
+/// Interlacing, pixel aspect ratio and `X` tags are not part of `VideoInfo`,
+/// they are set through the `interlace`, `aspect` and `x` options. A full
+/// range pixel format adds `XCOLORRANGE=FULL`.
+#[derive(Default)]
+pub struct Y4MMuxer {
+    info: Option<GlobalInfo>,
+    header: Option<Y4MHeader>,
+    interlace: Interlace,
+    aspect: Ratio,
+    extensions: Vec<String>,
+}
+
+impl Y4MMuxer {
+    pub fn new() -> Y4MMuxer {
+        Default::default()
+    }
+
+    /// Header written by the muxer, available once configured
+    pub fn header(&self) -> Option<&Y4MHeader> {
+        self.header.as_ref()
+    }
+}
+
+impl Muxer for Y4MMuxer {
+    fn configure(&mut self) -> Result<()> {
+        let info = self.info.as_ref().ok_or(Error::InvalidData)?;
+        let (stream, video) = info
+            .streams
+            .iter()
+            .find_map(|st| match st.params.kind {
+                Some(MediaKind::Video(ref video)) => Some((st, video)),
+                _ => None,
+            })
+            .ok_or(Error::InvalidData)?;
+
+        let colorspace = match video.format {
+            Some(ref fmt) => Colorspace::from_formaton(fmt).ok_or_else(|| {
+                error!("unsupported pixel format {:?}", fmt);
+                Error::InvalidData
+            })?,
+            None => Colorspace::default(),
+        };
+
+        let mut extensions = self.extensions.clone();
+        let full_range = video.format.as_deref().is_some_and(is_full_range);
+        if full_range && !extensions.iter().any(|x| x == FULL_RANGE) {
+            extensions.push(FULL_RANGE.to_owned());
+        }
+
+        // The timebase is expected to tick once per frame
+        let tb = stream.timebase;
+        let term = |t: i64| usize::try_from(t).map_err(|_| Error::InvalidData);
+        let framerate = Ratio::new(term(*tb.denom())?, term(*tb.numer())?);
+
+        let header = Y4MHeader {
+            width: video.width,
+            height: video.height,
+            framerate,
+            interlace: self.interlace,
+            aspect: self.aspect,
+            colorspace,
+            extensions,
+        };
+        if let Err(e) = header.check() {
+            error!("cannot write header {}: {}", header, e);
+            return Err(Error::InvalidData);
+        }
+        debug!("configured header: {}", header);
+        self.header = Some(header);
+
+        Ok(())
+    }
+
+    fn write_header(&mut self, out: &mut dyn Write) -> Result<()> {
+        let header = self.header.as_ref().ok_or(Error::InvalidData)?;
+        writeln!(out, "{}", header)?;
+
+        Ok(())
+    }
+
+    fn write_packet(&mut self, out: &mut dyn Write, pkt: Arc<Packet>) -> Result<()> {
+        let header = self.header.as_ref().ok_or(Error::InvalidData)?;
+        let data = payload(&pkt);
+        if data.len() != header.frame_size() {
+            error!(
+                "packet of {} bytes, expected {} bytes frames",
+                data.len(),
+                header.frame_size()
+            );
+            return Err(Error::InvalidData);
+        }
+
+        let params = FrameParams::from_packet(&pkt);
+        if let Some(params) = params {
+            for x in params.extensions() {
+                check_extension(x).map_err(|e| {
+                    error!("invalid frame parameter X{:?}: {}", x, e);
+                    Error::InvalidData
+                })?;
+            }
+        }
+
+        out.write_all(b"FRAME")?;
+        if let Some(params) = params {
+            write!(out, "{}", params)?;
+        }
+        out.write_all(b"\n")?;
+        out.write_all(data)?;
+
+        Ok(())
+    }
+
+    fn write_trailer(&mut self, _out: &mut dyn Write) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_global_info(&mut self, info: GlobalInfo) -> Result<()> {
+        self.info = Some(info);
+        Ok(())
+    }
+
+    fn set_option<'a>(&mut self, key: &str, val: Value<'a>) -> Result<()> {
+        match (key, val) {
+            ("interlace", Value::Str(val)) => {
+                self.interlace = Interlace::from_tag(val).ok_or(Error::InvalidData)?;
+            }
+            ("aspect", Value::Str(val)) => {
+                self.aspect = parse_ratio(val).ok_or(Error::InvalidData)?;
+            }
+            ("x", Value::Str(val)) => {
+                check_extension(val).map_err(|e| {
+                    error!("invalid x option {:?}: {}", val, e);
+                    Error::InvalidData
+                })?;
+                self.extensions.push(val.to_owned());
+            }
+            _ => return Err(Error::InvalidData),
+        }
+
+        Ok(())
+    }
+}
+
+struct Des {
+    d: Descr,
+}
+
+impl Descriptor for Des {
+    fn create(&self) -> Box<dyn Muxer> {
+        Box::new(Y4MMuxer::new())
+    }
+    fn describe(&self) -> &Descr {
+        &self.d
+    }
+}
+
+/// used by av context
+pub const Y4M_MUX_DESC: &dyn Descriptor = &Des {
+    d: Descr {
+        name: "y4m-rs",
+        demuxer: "y4m",
+        description: "Y4M muxer",
+        extensions: &["y4m"],
+        mime: &[],
+    },
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demuxer::Y4M_DESC;
+    use av_data::rational::Rational64;
+    use av_format::buffer::AccReader;
+    use av_format::demuxer::{Context, Event};
+    use std::io::Cursor;
+
+    const Y4M: &[u8] = include_bytes!("../assets/test.y4m");
+
+    #[test]
+    fn remux() {
+        let _ = pretty_env_logger::try_init();
+
+        let input = Box::new(AccReader::new(Cursor::new(Y4M)));
+        let mut demuxer = Context::new(Y4M_DESC.create(), input);
+        demuxer.read_headers().unwrap();
+
+        let mut muxer = Y4M_MUX_DESC.create();
+        muxer.set_global_info(demuxer.info.clone()).unwrap();
+        muxer.configure().unwrap();
+
+        let mut out = Vec::new();
+        muxer.write_header(&mut out).unwrap();
+        assert_eq!(
+            &out[..],
+            &b"YUV4MPEG2 W384 H288 F25:1 Ip A0:0 C420jpeg\n"[..]
+        );
+        let header_len = out.len();
+
+        loop {
+            match demuxer.read_event().unwrap() {
+                Event::NewPacket(pkt) => muxer.write_packet(&mut out, Arc::new(pkt)).unwrap(),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        muxer.write_trailer(&mut out).unwrap();
+
+        // The source header has no C tag
+        assert_eq!(&out[header_len..], &Y4M[34..]);
+
+        // The colour range is carried by the pixel format
+        let input = b"YUV4MPEG2 W2 H2 XCOLORRANGE=FULL\nFRAME\n012345";
+        let input = Box::new(AccReader::new(Cursor::new(&input[..])));
+        let mut demuxer = Context::new(Y4M_DESC.create(), input);
+        demuxer.read_headers().unwrap();
+
+        let mut muxer = Y4M_MUX_DESC.create();
+        muxer.set_global_info(demuxer.info.clone()).unwrap();
+        muxer.configure().unwrap();
+
+        let mut out = Vec::new();
+        muxer.write_header(&mut out).unwrap();
+        assert_eq!(
+            &out[..],
+            &b"YUV4MPEG2 W2 H2 F25:1 Ip A0:0 C420jpeg XCOLORRANGE=FULL\n"[..]
+        );
+    }
+
+    #[test]
+    fn write_frame_params() {
+        let input = Box::new(AccReader::new(Cursor::new(Y4M)));
+        let mut demuxer = Context::new(Y4M_DESC.create(), input);
+        demuxer.read_headers().unwrap();
+
+        let mut muxer = Y4MMuxer::new();
+        muxer.set_global_info(demuxer.info.clone()).unwrap();
+        muxer.set_option("interlace", Value::Str("m")).unwrap();
+        muxer.configure().unwrap();
+
+        let mut pkt = match demuxer.read_event().unwrap() {
+            Event::NewPacket(pkt) => pkt,
+            _ => panic!("expected a packet"),
+        };
+        let params = FrameParams::new()
+            .with_interlace(Interlace::TopFieldFirst)
+            .with_extension("foo=bar");
+        pkt.t.user_private = Some(Arc::new(params));
+
+        let mut out = Vec::new();
+        muxer.write_packet(&mut out, Arc::new(pkt)).unwrap();
+        assert!(out.starts_with(b"FRAME It Xfoo=bar\n"));
+        assert_eq!(out.len(), 18 + 384 * 288 * 3 / 2);
+    }
+
+    #[test]
+    fn reject_mismatched_packet() {
+        let input = Box::new(AccReader::new(Cursor::new(Y4M)));
+        let mut demuxer = Context::new(Y4M_DESC.create(), input);
+        demuxer.read_headers().unwrap();
+
+        let mut muxer = Y4MMuxer::new();
+        muxer.set_global_info(demuxer.info.clone()).unwrap();
+        muxer.configure().unwrap();
+
+        let mut out = Vec::new();
+        let pkt = Arc::new(Packet::with_capacity(16));
+        assert!(muxer.write_packet(&mut out, pkt).is_err());
+        assert!(out.is_empty());
+        assert!(muxer.set_option("interlace", Value::Str("z")).is_err());
+        assert!(muxer.set_option("x", Value::Str("a b")).is_err());
+        let aspect = Value::Str("1:4294967296");
+        assert!(muxer.set_option("aspect", aspect).is_err());
+
+        let mut pkt = Packet::with_capacity(384 * 288 * 3 / 2);
+        pkt.data.resize(384 * 288 * 3 / 2, 0);
+        let params = FrameParams::new().with_extension("foo\nFRAME");
+        pkt.t.user_private = Some(Arc::new(params));
+        assert!(muxer.write_packet(&mut out, Arc::new(pkt)).is_err());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn reject_invalid_stream() {
+        let input = Box::new(AccReader::new(Cursor::new(Y4M)));
+        let mut demuxer = Context::new(Y4M_DESC.create(), input);
+        demuxer.read_headers().unwrap();
+
+        let configure = |info: GlobalInfo| {
+            let mut muxer = Y4MMuxer::new();
+            muxer.set_global_info(info).unwrap();
+            muxer.configure()
+        };
+
+        let mut info = demuxer.info.clone();
+        info.streams[0].timebase = Rational64::new(-1, 25);
+        assert!(configure(info).is_err());
+
+        let mut info = demuxer.info.clone();
+        info.streams[0].timebase = Rational64::new(1, 1 << 40);
+        assert!(configure(info).is_err());
+
+        let mut info = demuxer.info.clone();
+        if let Some(MediaKind::Video(ref mut video)) = info.streams[0].params.kind {
+            video.width = 0;
+        }
+        assert!(configure(info).is_err());
+
+        assert!(configure(demuxer.info.clone()).is_ok());
+    }
+}
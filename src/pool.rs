@@ -0,0 +1,218 @@
+//! Recycled frame buffers shared without copies
+//!
+//! [`Reader::next_pooled_frame`] reads frames straight into pooled buffers,
+//! and [`Prefetcher`] builds on it. [`Y4MDemuxer::with_pool`] hands out
+//! pooled frames along its packets, which the muxer and decoder of this crate
+//! accept as is.
+//!
+//! [`Reader::next_pooled_frame`]: crate::Reader::next_pooled_frame
+//! [`Prefetcher`]: crate::prefetch::Prefetcher
+//! [`Y4MDemuxer::with_pool`]: crate::demuxer::Y4MDemuxer::with_pool
+
+use crate::demuxer::{Colorspace, FrameParams};
+use crate::reader::Frame;
+use av_data::packet::Packet;
+use std::borrow::Cow;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+
+type Buffers = Mutex<Vec<Vec<u8>>>;
+
+/// Locks the idle buffers, which stay usable even if a holder of the lock
+/// panicked
+fn lock(buffers: &Buffers) -> MutexGuard<'_, Vec<Vec<u8>>> {
+    buffers.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Hands out frame buffers and takes them back once their frames are dropped
+#[derive(Clone)]
+pub struct FramePool {
+    buffers: Arc<Buffers>,
+    capacity: usize,
+}
+
+impl FramePool {
+    /// Pool keeping up to `capacity` idle buffers
+    pub fn new(capacity: usize) -> FramePool {
+        FramePool {
+            buffers: Arc::new(Mutex::new(Vec::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Number of idle buffers
+    pub fn available(&self) -> usize {
+        lock(&self.buffers).len()
+    }
+
+    /// An idle buffer, or a new one if none is left, of `size` bytes
+    pub(crate) fn get(&self, size: usize) -> PooledBuffer {
+        let mut data = lock(&self.buffers).pop().unwrap_or_default();
+        data.resize(size, 0);
+        PooledBuffer {
+            data,
+            pool: Arc::downgrade(&self.buffers),
+            capacity: self.capacity,
+        }
+    }
+}
+
+/// A buffer going back to its pool when dropped
+pub(crate) struct PooledBuffer {
+    pub(crate) data: Vec<u8>,
+    pool: Weak<Buffers>,
+    capacity: usize,
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.upgrade() {
+            let mut buffers = lock(&pool);
+            if buffers.len() < self.capacity {
+                buffers.push(std::mem::take(&mut self.data));
+            }
+        }
+    }
+}
+
+/// A frame held in a pooled buffer
+///
+/// Clones share the buffer, which goes back to the pool once the last of them
+/// is dropped.
+#[derive(Clone)]
+pub struct PooledFrame {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) colorspace: Colorspace,
+    pub(crate) params: FrameParams,
+    pub(crate) buffer: Arc<PooledBuffer>,
+}
+
+impl PooledFrame {
+    /// Borrows the frame, to access its planes
+    pub fn as_frame(&self) -> Frame<'_> {
+        Frame {
+            width: self.width,
+            height: self.height,
+            colorspace: self.colorspace,
+            params: self.params.clone(),
+            data: Cow::Borrowed(&self.buffer.data),
+        }
+    }
+
+    /// Parameters following the `FRAME` marker
+    pub fn params(&self) -> &FrameParams {
+        &self.params
+    }
+
+    /// Frame carried by a packet of a pooled demuxer, if any
+    pub fn from_packet(pkt: &Packet) -> Option<&PooledFrame> {
+        pkt.t.user_private.as_ref()?.downcast_ref()
+    }
+}
+
+/// Frame data of a packet, pooled or not
+pub(crate) fn payload(pkt: &Packet) -> &[u8] {
+    match PooledFrame::from_packet(pkt) {
+        Some(frame) => frame,
+        None => &pkt.data,
+    }
+}
+
+/// Data of all the planes
+impl Deref for PooledFrame {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demuxer::Y4MDemuxer;
+    use crate::muxer::Y4MMuxer;
+    use crate::Reader;
+    use av_format::buffer::AccReader;
+    use av_format::demuxer::{Context, Event};
+    use av_format::muxer::Muxer;
+    use std::io::Cursor;
+
+    const Y4M: &[u8] = include_bytes!("../assets/test.y4m");
+
+    #[test]
+    fn recycle_buffers() {
+        let pool = FramePool::new(2);
+        let mut reader = Reader::new(Cursor::new(Y4M)).unwrap();
+
+        let first = reader.next_pooled_frame(&pool).unwrap().unwrap();
+        let shared = first.clone();
+        let second = reader.next_pooled_frame(&pool).unwrap().unwrap();
+        assert_eq!(&first[..], &Y4M[40..40 + 165_888]);
+        assert_eq!(second.as_frame().plane(1).unwrap().len(), 192 * 144);
+        assert_eq!(pool.available(), 0);
+
+        drop(first);
+        assert_eq!(pool.available(), 0);
+        let ptr = shared.as_ptr();
+        drop(shared);
+        assert_eq!(pool.available(), 1);
+
+        // The buffer is reused rather than allocated
+        let third = reader.next_pooled_frame(&pool).unwrap().unwrap();
+        assert_eq!(third.as_ptr(), ptr);
+        drop((second, third));
+        assert_eq!(pool.available(), 2);
+
+        let mut count = 3;
+        while let Some(frame) = reader.next_pooled_frame(&pool).unwrap() {
+            assert_eq!(frame.len(), 165_888);
+            count += 1;
+        }
+        assert_eq!(count, 51);
+        assert_eq!(pool.available(), 2);
+    }
+    #[test]
+    fn demux_pooled() {
+        let pool = FramePool::new(2);
+        let input = Box::new(AccReader::new(Cursor::new(Y4M)));
+        let demuxer = Y4MDemuxer::new().with_pool(pool.clone());
+        let mut demuxer = Context::new(Box::new(demuxer), input);
+        demuxer.read_headers().unwrap();
+
+        let mut muxer = Y4MMuxer::new();
+        muxer.set_global_info(demuxer.info.clone()).unwrap();
+        muxer.configure().unwrap();
+        let mut out = Vec::new();
+        muxer.write_header(&mut out).unwrap();
+        let header_len = out.len();
+
+        let mut ptr = None;
+        let mut count = 0;
+        loop {
+            match demuxer.read_event().unwrap() {
+                Event::NewPacket(pkt) => {
+                    assert!(pkt.data.is_empty());
+                    let frame = PooledFrame::from_packet(&pkt).unwrap().clone();
+                    assert_eq!(frame.len(), 165_888);
+                    assert_eq!(frame.as_frame().plane(2).unwrap().len(), 192 * 144);
+
+                    // Each packet reuses the buffer of the previous one
+                    assert_eq!(*ptr.get_or_insert(frame.as_ptr()), frame.as_ptr());
+                    muxer.write_packet(&mut out, Arc::new(pkt)).unwrap();
+                    assert_eq!(pool.available(), 0);
+                    drop(frame);
+                    assert_eq!(pool.available(), 1);
+                    count += 1;
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(count, 51);
+
+        // The source header has no C tag
+        assert_eq!(&out[header_len..], &Y4M[34..]);
+    }
+}
@@ -0,0 +1,157 @@
+//! Read-ahead of frames on a worker thread
+
+use crate::demuxer::Y4MHeader;
+use crate::error::Y4MError;
+use crate::pool::{FramePool, PooledFrame};
+use crate::Reader;
+use std::io::{self, Read};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread;
+
+/// Bounds on the frames read ahead
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadAhead {
+    /// Maximum number of frames read ahead
+    pub depth: usize,
+    /// Maximum number of bytes of frame data read ahead
+    ///
+    /// At least one frame is always read ahead.
+    pub max_memory: usize,
+}
+
+impl Default for ReadAhead {
+    fn default() -> Self {
+        ReadAhead {
+            depth: 4,
+            max_memory: 256 << 20,
+        }
+    }
+}
+
+/// Reads frames ahead of time on a worker thread
+///
+/// Frames, errors and the end of the stream are reported in order. The worker
+/// stops after the first error, or once the prefetcher is dropped.
+pub struct Prefetcher {
+    header: Y4MHeader,
+    warnings: Vec<Y4MError>,
+    depth: usize,
+    frames: Receiver<io::Result<Option<PooledFrame>>>,
+    done: bool,
+}
+
+impl Prefetcher {
+    /// Starts reading the frames of `reader` on a new thread
+    pub fn new<R: Read + Send + 'static>(mut reader: Reader<R>, options: ReadAhead) -> Prefetcher {
+        let header = reader.header().clone();
+        let warnings = reader.warnings().to_vec();
+        let frame_size = header.frame_size().max(1);
+        let depth = options.depth.min(options.max_memory / frame_size).max(1);
+
+        // The worker holds one more frame while waiting for room in the queue
+        let (tx, frames) = sync_channel(depth - 1);
+        let pool = FramePool::new(depth + 1);
+        thread::spawn(move || loop {
+            let res = reader.next_pooled_frame(&pool);
+            let last = !matches!(res, Ok(Some(_)));
+            if tx.send(res).is_err() || last {
+                break;
+            }
+        });
+
+        Prefetcher {
+            header,
+            warnings,
+            depth,
+            frames,
+            done: false,
+        }
+    }
+
+    pub fn header(&self) -> &Y4MHeader {
+        &self.header
+    }
+
+    /// Quirks accepted while reading the stream header, in lenient mode
+    pub fn warnings(&self) -> &[Y4MError] {
+        &self.warnings
+    }
+
+    /// Number of frames read ahead, within the memory cap
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Next frame, `None` at the end of the stream
+    pub fn next_frame(&mut self) -> io::Result<Option<PooledFrame>> {
+        if self.done {
+            return Ok(None);
+        }
+        let res = self
+            .frames
+            .recv()
+            .unwrap_or_else(|_| Err(io::Error::other("read-ahead thread stopped unexpectedly")));
+        if !matches!(res, Ok(Some(_))) {
+            self.done = true;
+        }
+        res
+    }
+}
+
+impl Iterator for Prefetcher {
+    type Item = io::Result<PooledFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const Y4M: &[u8] = include_bytes!("../assets/test.y4m");
+
+    #[test]
+    fn read_ahead() {
+        let reader = Reader::new(Cursor::new(Y4M)).unwrap();
+        let prefetcher = Prefetcher::new(reader, ReadAhead::default());
+        assert_eq!(prefetcher.depth(), 4);
+        assert_eq!(prefetcher.header().width(), 384);
+
+        let mut direct = Reader::new(Cursor::new(Y4M)).unwrap();
+        let mut count = 0;
+        for frame in prefetcher {
+            let frame = frame.unwrap();
+            assert_eq!(&frame[..], direct.next_frame().unwrap().unwrap().data());
+            count += 1;
+        }
+        assert_eq!(count, 51);
+
+        let reader = Reader::new(Cursor::new(Y4M)).unwrap();
+        let options = ReadAhead {
+            depth: 8,
+            max_memory: 3 * 165_888,
+        };
+        assert_eq!(Prefetcher::new(reader, options).depth(), 3);
+    }
+
+    #[test]
+    fn errors_in_order() {
+        let data = b"YUV4MPEG2 W1 H1 Cmono\nFRAME\n\x01FRAME\n\x02FRAMF\n\x03".to_vec();
+        let reader = Reader::new(Cursor::new(data)).unwrap();
+        let mut prefetcher = Prefetcher::new(
+            reader,
+            ReadAhead {
+                depth: 1,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(&prefetcher.next_frame().unwrap().unwrap()[..], &[1]);
+        assert_eq!(&prefetcher.next_frame().unwrap().unwrap()[..], &[2]);
+        assert!(prefetcher.next_frame().is_err());
+        assert!(prefetcher.next_frame().unwrap().is_none());
+    }
+}
@@ -0,0 +1,489 @@
+use crate::demuxer::{
+    frame_header, parse_header, Colorspace, FrameParams, Limits, ParseMode, Y4MHeader,
+};
+use crate::error::Y4MError;
+use crate::pool::{FramePool, PooledBuffer, PooledFrame};
+use std::borrow::Cow;
+use std::io::{self, ErrorKind, Read};
+use std::sync::Arc;
+
+/// Reads a YUV4MPEG2 stream without going through `av_format`
+///
+/// The input is only read forward, pipes and stdin work as well as files.
+/// Lines are read one byte at a time, wrap unbuffered inputs such as files or
+/// sockets in a `BufReader`.
+///
+/// Errors found in the stream are reported as `InvalidData` I/O errors
+/// carrying a [`Y4MError`].
+pub struct Reader<R: Read> {
+    input: R,
+    state: ReadState,
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads the stream header with the default mode and limits
+    pub fn new(input: R) -> io::Result<Reader<R>> {
+        Reader::with_options(input, ParseMode::default(), Limits::default())
+    }
+
+    /// Reads the stream header, parsing and bounding the stream as requested
+    pub fn with_options(mut input: R, mode: ParseMode, limits: Limits) -> io::Result<Reader<R>> {
+        let mut line = Vec::new();
+        read_line(&mut input, &mut line, header_line_len(&limits))?;
+        let state = ReadState::new(line, mode, limits)?;
+
+        Ok(Reader { input, state })
+    }
+
+    pub fn header(&self) -> &Y4MHeader {
+        &self.state.header
+    }
+
+    /// Quirks accepted while reading the stream header, in lenient mode
+    pub fn warnings(&self) -> &[Y4MError] {
+        &self.state.warnings
+    }
+
+    /// Reads the next frame, `None` at the end of the stream
+    ///
+    /// The frame borrows the buffer of the reader, reused from one frame to
+    /// the next, use [`Frame::into_owned`] to keep it.
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame<'_>>> {
+        let params = match self.read_frame_header()? {
+            Some(params) => params,
+            None => return Ok(None),
+        };
+        let data = self.state.frame_buffer();
+        self.input.read_exact(data)?;
+
+        Ok(Some(self.state.frame(params)))
+    }
+
+    /// Reads the next frame into `frame`, reusing its buffer, `false` at the
+    /// end of the stream
+    pub fn read_frame_into(&mut self, frame: &mut Frame<'static>) -> io::Result<bool> {
+        let params = match self.read_frame_header()? {
+            Some(params) => params,
+            None => return Ok(false),
+        };
+        let data = self.state.frame_buffer_in(frame);
+        self.input.read_exact(data)?;
+        self.state.fill_frame(frame, params);
+
+        Ok(true)
+    }
+
+    /// Reads the next frame into a buffer taken from `pool`, `None` at the
+    /// end of the stream
+    pub fn next_pooled_frame(&mut self, pool: &FramePool) -> io::Result<Option<PooledFrame>> {
+        let params = match self.read_frame_header()? {
+            Some(params) => params,
+            None => return Ok(None),
+        };
+        let mut buffer = pool.get(self.state.header.frame_size());
+        self.input.read_exact(&mut buffer.data)?;
+
+        Ok(Some(self.state.pooled_frame(buffer, params)))
+    }
+
+    /// Iterates over the remaining frames, each in its own buffer
+    ///
+    /// Iteration ends after the first error.
+    pub fn frames(&mut self) -> Frames<'_, R> {
+        Frames {
+            reader: self,
+            done: false,
+        }
+    }
+
+    fn read_frame_header(&mut self) -> io::Result<Option<FrameParams>> {
+        let max = self.state.frame_line_len();
+        read_line(&mut self.input, &mut self.state.line, max)?;
+        self.state.frame_params()
+    }
+
+    /// Offset in the stream of the next frame
+    pub fn position(&self) -> usize {
+        self.state.pos
+    }
+
+    /// Returns the underlying reader, positioned at the next frame
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+/// Iterator over the frames of a [`Reader`]
+pub struct Frames<'a, R: Read> {
+    reader: &'a mut Reader<R>,
+    done: bool,
+}
+
+impl<R: Read> Iterator for Frames<'_, R> {
+    type Item = io::Result<Frame<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut frame = Frame::default();
+        match self.reader.read_frame_into(&mut frame) {
+            Ok(true) => Some(Ok(frame)),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Everything a reader keeps but its input, shared by [`Reader`] and its
+/// asynchronous counterpart, which only differ in how they read
+pub(crate) struct ReadState {
+    pub(crate) header: Y4MHeader,
+    mode: ParseMode,
+    limits: Limits,
+    pub(crate) warnings: Vec<Y4MError>,
+    /// Offset of the next frame
+    pub(crate) pos: usize,
+    /// Last line read
+    pub(crate) line: Vec<u8>,
+    /// Data of the frames borrowed from the reader
+    data: Vec<u8>,
+}
+
+impl ReadState {
+    /// Parses the stream header read into `line`
+    pub(crate) fn new(line: Vec<u8>, mode: ParseMode, limits: Limits) -> io::Result<ReadState> {
+        let mut warnings = Vec::new();
+        let header = stream_header(&line, mode, &limits, &mut warnings)?;
+
+        Ok(ReadState {
+            header,
+            mode,
+            limits,
+            warnings,
+            pos: line.len(),
+            line,
+            data: Vec::new(),
+        })
+    }
+
+    /// Longest frame header line to read into `line`
+    pub(crate) fn frame_line_len(&self) -> usize {
+        frame_line_len(&self.limits)
+    }
+
+    /// Parses the frame header read into `line`, `None` at the end of the
+    /// stream
+    pub(crate) fn frame_params(&mut self) -> io::Result<Option<FrameParams>> {
+        if self.line.is_empty() {
+            return Ok(None);
+        }
+        let params = frame_params(&self.line, self.mode, &self.limits, self.pos)?;
+        self.pos += self.line.len();
+
+        Ok(Some(params))
+    }
+
+    /// Buffer of the reader to read the next frame data into
+    pub(crate) fn frame_buffer(&mut self) -> &mut [u8] {
+        self.data.resize(self.header.frame_size(), 0);
+        &mut self.data
+    }
+
+    /// Buffer of `frame` to read the next frame data into
+    pub(crate) fn frame_buffer_in<'f>(&self, frame: &'f mut Frame<'static>) -> &'f mut [u8] {
+        let data = frame.data.to_mut();
+        data.resize(self.header.frame_size(), 0);
+        data
+    }
+
+    /// Frame whose data was read into [`frame_buffer`](Self::frame_buffer)
+    pub(crate) fn frame(&mut self, params: FrameParams) -> Frame<'_> {
+        self.pos += self.data.len();
+        Frame {
+            width: self.header.width,
+            height: self.header.height,
+            colorspace: self.header.colorspace,
+            params,
+            data: Cow::Borrowed(&self.data),
+        }
+    }
+
+    /// Completes `frame`, whose data was read into
+    /// [`frame_buffer_in`](Self::frame_buffer_in)
+    pub(crate) fn fill_frame(&mut self, frame: &mut Frame<'static>, params: FrameParams) {
+        self.pos += frame.data.len();
+        frame.width = self.header.width;
+        frame.height = self.header.height;
+        frame.colorspace = self.header.colorspace;
+        frame.params = params;
+    }
+
+    /// Frame whose data was read into `buffer`
+    pub(crate) fn pooled_frame(
+        &mut self,
+        buffer: PooledBuffer,
+        params: FrameParams,
+    ) -> PooledFrame {
+        self.pos += buffer.data.len();
+        PooledFrame {
+            width: self.header.width,
+            height: self.header.height,
+            colorspace: self.header.colorspace,
+            params,
+            buffer: Arc::new(buffer),
+        }
+    }
+}
+
+/// Longest stream header line to read, one byte past the limit to tell a
+/// header going over it
+pub(crate) fn header_line_len(limits: &Limits) -> usize {
+    limits.max_header_len.saturating_add(1)
+}
+
+/// Longest frame header line to read, one byte past the limit
+pub(crate) fn frame_line_len(limits: &Limits) -> usize {
+    limits.max_frame_header_len().saturating_add(1)
+}
+
+/// Parses the stream header found in `line`
+pub(crate) fn stream_header(
+    line: &[u8],
+    mode: ParseMode,
+    limits: &Limits,
+    warnings: &mut Vec<Y4MError>,
+) -> io::Result<Y4MHeader> {
+    let header = match parse_header(line, mode, warnings) {
+        Ok((_, header)) => header,
+        Err(Y4MError::TruncatedHeader { .. }) if line.len() > limits.max_header_len => {
+            return Err(Y4MError::LimitExceeded {
+                what: "header length",
+                limit: limits.max_header_len,
+            }
+            .into())
+        }
+        Err(e) => return Err(e.into()),
+    };
+    limits.check_header(&header, line.len())?;
+
+    Ok(header)
+}
+
+/// Parses the frame header found in `line`, read at offset `pos`
+pub(crate) fn frame_params(
+    line: &[u8],
+    mode: ParseMode,
+    limits: &Limits,
+    pos: usize,
+) -> io::Result<FrameParams> {
+    let params = match frame_header(line, mode) {
+        Ok((_, params)) => params,
+        Err(nom::Err::Incomplete(_)) if line.len() > limits.max_frame_header_len() => {
+            return Err(Y4MError::LimitExceeded {
+                what: "frame parameters length",
+                limit: limits.max_frame_params_len,
+            }
+            .into())
+        }
+        Err(nom::Err::Incomplete(_)) => return Err(ErrorKind::UnexpectedEof.into()),
+        Err(_) => return Err(Y4MError::InvalidFrameHeader { offset: pos }.into()),
+    };
+    limits.check_frame_params(&params, line.len())?;
+
+    Ok(params)
+}
+
+/// Reads up to the next newline included, at most `max` bytes
+///
+/// Bytes are read one at a time so that nothing past the line is consumed.
+fn read_line<R: Read>(input: &mut R, line: &mut Vec<u8>, max: usize) -> io::Result<()> {
+    let mut byte = [0u8; 1];
+    line.clear();
+    while line.len() < max && line.last() != Some(&b'\n') {
+        match input.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) => line.push(byte[0]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// A frame read by [`Reader`], borrowing its data or owning it
+///
+/// Planes are stored one after the other without padding, samples wider than
+/// 8 bits take two little-endian bytes.
+#[derive(Clone, Debug, Default)]
+pub struct Frame<'a> {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) colorspace: Colorspace,
+    pub(crate) params: FrameParams,
+    pub(crate) data: Cow<'a, [u8]>,
+}
+
+impl Frame<'_> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn colorspace(&self) -> Colorspace {
+        self.colorspace
+    }
+
+    /// Parameters following the `FRAME` marker
+    pub fn params(&self) -> &FrameParams {
+        &self.params
+    }
+
+    /// Data of all the planes
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Data of the given plane, `None` if the colorspace lacks it
+    pub fn plane(&self, plane: usize) -> Option<&[u8]> {
+        if plane >= self.colorspace.planes() {
+            return None;
+        }
+        let layout = self.layout();
+        let start = (0..plane).map(|p| layout.plane_size(p)).sum::<usize>();
+        Some(&self.data[start..start + layout.plane_size(plane)])
+    }
+
+    /// Length in bytes of the rows of the given plane
+    pub fn stride(&self, plane: usize) -> Option<usize> {
+        if plane >= self.colorspace.planes() {
+            return None;
+        }
+        let (width, _) = self.layout().plane_dimensions(plane);
+        Some(width * self.colorspace.bytes_per_sample())
+    }
+
+    /// Copies the data if it is borrowed
+    pub fn into_owned(self) -> Frame<'static> {
+        Frame {
+            width: self.width,
+            height: self.height,
+            colorspace: self.colorspace,
+            params: self.params,
+            data: Cow::Owned(self.data.into_owned()),
+        }
+    }
+
+    fn layout(&self) -> Y4MHeader {
+        Y4MHeader {
+            width: self.width,
+            height: self.height,
+            colorspace: self.colorspace,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demuxer::Interlace;
+    use std::io::Cursor;
+
+    const Y4M: &[u8] = include_bytes!("../assets/test.y4m");
+
+    #[test]
+    fn read_frames() {
+        let mut reader = Reader::new(Cursor::new(Y4M)).unwrap();
+        assert_eq!(reader.header().width(), 384);
+        assert_eq!(reader.header().height(), 288);
+
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.next_frame().unwrap() {
+            assert!(frame.params().is_empty());
+            assert_eq!(frame.data().len(), 165888);
+            frames.push(frame.into_owned());
+        }
+        assert_eq!(frames.len(), 51);
+
+        let frame = &frames[0];
+        assert_eq!(frame.plane(0).unwrap(), &Y4M[40..40 + 384 * 288]);
+        assert_eq!(frame.plane(2).unwrap().len(), 192 * 144);
+        assert_eq!(frame.stride(1), Some(192));
+        assert_eq!(frame.plane(3), None);
+    }
+
+    #[test]
+    fn read_frame_params() {
+        let mut data = b"YUV4MPEG2 W3 H1 C444 It\nFRAME Ib Xa=b\n".to_vec();
+        data.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let mut reader = Reader::new(&data[..]).unwrap();
+        assert_eq!(reader.header().interlace(), Interlace::TopFieldFirst);
+
+        let frame = reader.next_frame().unwrap().unwrap();
+        assert_eq!(
+            frame.params().interlace(),
+            Some(Interlace::BottomFieldFirst)
+        );
+        assert_eq!(frame.params().extensions(), ["a=b"]);
+        assert_eq!(frame.plane(1).unwrap(), &[4, 5, 6]);
+        assert!(reader.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn iterate_frames() {
+        let mut a = Reader::new(Cursor::new(Y4M)).unwrap();
+        let mut b = Reader::new(Cursor::new(Y4M)).unwrap();
+        let frames = a.frames().step_by(2).zip(b.frames().skip(1).step_by(2));
+        let mut count = 0;
+        for (a, b) in frames {
+            let (a, b) = (a.unwrap(), b.unwrap());
+            assert_eq!(a.data().len(), b.data().len());
+            count += 1;
+        }
+        assert_eq!(count, 25);
+
+        let mut reader = Reader::new(Cursor::new(Y4M)).unwrap();
+        let mut frame = Frame::default();
+        let mut count = 0;
+        while reader.read_frame_into(&mut frame).unwrap() {
+            assert_eq!(frame.plane(1).unwrap().len(), 192 * 144);
+            count += 1;
+        }
+        assert_eq!(count, 51);
+        assert!(reader.frames().next().is_none());
+
+        let mut reader = Reader::new(&b"YUV4MPEG2 W1 H1 Cmono\nFRAME\n\0FRAMF\n"[..]).unwrap();
+        let frames: Vec<_> = reader.frames().collect();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].is_ok() && frames[1].is_err());
+    }
+
+    #[test]
+    fn read_errors() {
+        let err = |data: &[u8]| {
+            let mut reader = Reader::new(data)?;
+            while reader.next_frame()?.is_some() {}
+            Ok::<_, io::Error>(())
+        };
+        let typed = |e: io::Error| e.into_inner().unwrap().downcast::<Y4MError>().unwrap();
+
+        let e = err(b"YUV4MPEG2 W3 H1").unwrap_err();
+        assert_eq!(*typed(e), Y4MError::TruncatedHeader { offset: 15 });
+        let e = err(b"YUV4MPEG2 W3 H1\nFRAM").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+        let e = err(b"YUV4MPEG2 W3 H1\nFRAME\n\0").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+        let e = err(b"YUV4MPEG2 W1 H1 Cmono\nFRAME\n\0FRAMF\n\0").unwrap_err();
+        assert_eq!(*typed(e), Y4MError::InvalidFrameHeader { offset: 29 });
+    }
+}
@@ -0,0 +1,246 @@
+use crate::demuxer::{check_extension, Colorspace, FrameParams, Interlace, Ratio, Y4MHeader};
+use std::io::{self, ErrorKind, Write};
+
+/// Configures and creates a [`Writer`]
+#[derive(Clone, Debug)]
+pub struct WriterBuilder {
+    header: Y4MHeader,
+}
+
+fn invalid_input(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, msg)
+}
+
+impl WriterBuilder {
+    /// Progressive 25 fps 4:2:0 stream of the given dimensions
+    pub fn new(width: usize, height: usize) -> WriterBuilder {
+        WriterBuilder {
+            header: Y4MHeader {
+                width,
+                height,
+                framerate: Ratio::new(25, 1),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Frames per second, as `num:den`
+    pub fn with_framerate(mut self, framerate: Ratio) -> WriterBuilder {
+        self.header.framerate = framerate;
+        self
+    }
+
+    pub fn with_colorspace(mut self, colorspace: Colorspace) -> WriterBuilder {
+        self.header.colorspace = colorspace;
+        self
+    }
+
+    pub fn with_interlace(mut self, interlace: Interlace) -> WriterBuilder {
+        self.header.interlace = interlace;
+        self
+    }
+
+    /// Pixel aspect ratio
+    pub fn with_aspect(mut self, aspect: Ratio) -> WriterBuilder {
+        self.header.aspect = aspect;
+        self
+    }
+
+    /// Adds an `X` tag, `extension` being its value without the leading `X`
+    pub fn with_extension<S: Into<String>>(mut self, extension: S) -> WriterBuilder {
+        self.header.extensions.push(extension.into());
+        self
+    }
+
+    /// Validates the configuration and writes the stream header to `output`
+    pub fn build<W: Write>(self, mut output: W) -> io::Result<Writer<W>> {
+        let header = self.into_header()?;
+        writeln!(output, "{}", header)?;
+
+        Ok(Writer { output, header })
+    }
+
+    pub(crate) fn into_header(self) -> io::Result<Y4MHeader> {
+        self.header.check().map_err(invalid_input)?;
+        Ok(self.header)
+    }
+}
+
+/// Checks that a frame can be written to the stream described by `header`
+pub(crate) fn check_frame(
+    header: &Y4MHeader,
+    planes: &[&[u8]],
+    strides: &[usize],
+    params: &FrameParams,
+) -> io::Result<()> {
+    let colorspace = header.colorspace;
+    if planes.len() != colorspace.planes() || strides.len() != colorspace.planes() {
+        return Err(invalid_input("plane count does not match the colorspace"));
+    }
+    for (plane, (data, &stride)) in planes.iter().zip(strides).enumerate() {
+        let (width, height) = header.plane_dimensions(plane);
+        let row = width * colorspace.bytes_per_sample();
+        let len = stride
+            .checked_mul(height - 1)
+            .and_then(|len| len.checked_add(row))
+            .ok_or_else(|| invalid_input("plane stride overflows"))?;
+        if stride < row || data.len() < len {
+            return Err(invalid_input("plane is too small for the stream"));
+        }
+    }
+    for x in params.extensions() {
+        check_extension(x).map_err(invalid_input)?;
+    }
+    Ok(())
+}
+
+/// Slices of `data` to write for the given plane, the whole plane at once if
+/// its rows are not padded
+pub(crate) fn plane_rows<'a>(
+    header: &Y4MHeader,
+    plane: usize,
+    data: &'a [u8],
+    stride: usize,
+) -> impl Iterator<Item = &'a [u8]> {
+    let (width, height) = header.plane_dimensions(plane);
+    let row = width * header.colorspace.bytes_per_sample();
+    let (chunk, len, count) = if stride == row {
+        (row * height, row * height, 1)
+    } else {
+        (stride, row, height)
+    };
+    data.chunks(chunk).take(count).map(move |rows| &rows[..len])
+}
+
+/// Writes a YUV4MPEG2 stream without going through `av_format`
+///
+/// Every write goes straight to the output, wrap it in a `BufWriter` if
+/// needed.
+pub struct Writer<W: Write> {
+    output: W,
+    header: Y4MHeader,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn header(&self) -> &Y4MHeader {
+        &self.header
+    }
+
+    /// Writes a frame without parameters
+    ///
+    /// See [`write_frame_with_params`](Self::write_frame_with_params).
+    pub fn write_frame(&mut self, planes: &[&[u8]], strides: &[usize]) -> io::Result<()> {
+        self.write_frame_with_params(planes, strides, &FrameParams::default())
+    }
+
+    /// Writes a frame, `strides` giving the length in bytes of the rows of
+    /// each of the `planes`
+    ///
+    /// Samples wider than 8 bits take two little-endian bytes. Nothing is
+    /// written if the planes do not match the stream.
+    pub fn write_frame_with_params(
+        &mut self,
+        planes: &[&[u8]],
+        strides: &[usize],
+        params: &FrameParams,
+    ) -> io::Result<()> {
+        check_frame(&self.header, planes, strides, params)?;
+
+        writeln!(self.output, "FRAME{}", params)?;
+        for (plane, (data, &stride)) in planes.iter().zip(strides).enumerate() {
+            for rows in plane_rows(&self.header, plane, data, stride) {
+                self.output.write_all(rows)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    #[test]
+    fn write_header() {
+        let writer = WriterBuilder::new(320, 240)
+            .with_framerate(Ratio::new(30000, 1001))
+            .with_colorspace(Colorspace::C422p10)
+            .with_interlace(Interlace::TopFieldFirst)
+            .with_aspect(Ratio::new(1, 1))
+            .with_extension("COLORRANGE=FULL")
+            .build(Vec::new())
+            .unwrap();
+        assert_eq!(
+            writer.into_inner(),
+            b"YUV4MPEG2 W320 H240 F30000:1001 It A1:1 C422p10 XCOLORRANGE=FULL\n"
+        );
+
+        assert!(WriterBuilder::new(0, 240).build(Vec::new()).is_err());
+        assert!(WriterBuilder::new(usize::MAX, 2).build(Vec::new()).is_err());
+        assert!(WriterBuilder::new(2, 2)
+            .with_extension("a b")
+            .build(Vec::new())
+            .is_err());
+        assert!(WriterBuilder::new(2, 2)
+            .with_framerate(Ratio::new(1 << 32, 1))
+            .build(Vec::new())
+            .is_err());
+    }
+
+    #[test]
+    fn write_roundtrip() {
+        let mut writer = WriterBuilder::new(3, 2)
+            .with_colorspace(Colorspace::C420mpeg2)
+            .build(Vec::new())
+            .unwrap();
+
+        // Rows padded to 4 bytes
+        let y: [u8; 8] = [1, 2, 3, 0, 4, 5, 6, 0];
+        let u: [u8; 2] = [7, 8];
+        let v: [u8; 4] = [9, 10, 0, 0];
+        writer.write_frame(&[&y, &u, &v], &[4, 2, 4]).unwrap();
+        let params = FrameParams::new().with_interlace(Interlace::BottomFieldFirst);
+        writer
+            .write_frame_with_params(&[&y[..7], &u, &v[..2]], &[4, 2, 2], &params)
+            .unwrap();
+
+        let out = writer.into_inner();
+        let mut reader = Reader::new(&out[..]).unwrap();
+        assert_eq!(reader.header().colorspace(), Colorspace::C420mpeg2);
+        let frame = reader.next_frame().unwrap().unwrap();
+        assert_eq!(frame.data(), &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        let frame = reader.next_frame().unwrap().unwrap();
+        assert_eq!(frame.params(), &params);
+        assert_eq!(frame.data(), &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert!(reader.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn reject_mismatched_planes() {
+        let mut writer = WriterBuilder::new(2, 2)
+            .with_colorspace(Colorspace::Cmono)
+            .build(Vec::new())
+            .unwrap();
+        let header_len = writer.output.len();
+
+        assert!(writer.write_frame(&[&[0; 4], &[0; 4]], &[2, 2]).is_err());
+        assert!(writer.write_frame(&[&[0; 3]], &[2]).is_err());
+        assert!(writer.write_frame(&[&[0; 4]], &[1]).is_err());
+        assert!(writer.write_frame(&[&[0; 4]], &[usize::MAX]).is_err());
+        let params = FrameParams::new().with_extension("a\nb");
+        assert!(writer
+            .write_frame_with_params(&[&[0; 4]], &[2], &params)
+            .is_err());
+        assert_eq!(writer.output.len(), header_len);
+    }
+}